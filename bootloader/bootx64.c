@@ -10,14 +10,109 @@ typedef struct {
 	uint32_t pixels_per_scan_line;
 } FrameBuffer;
 
+#define MAX_KERNEL_SEGMENTS 16
+
+//where a PT_LOAD segment of the kernel was placed, flags are the elf p_flags
+typedef struct {
+	uint64_t physical_start;
+	uint64_t virtual_start;
+	uint64_t size;
+	uint64_t flags;
+} KernelSegment;
+
 typedef struct {
 	FrameBuffer* frame_buffer;
 	EFI_MEMORY_DESCRIPTOR* memory_map;
 	uint64_t memory_map_size;
 	uint64_t descriptor_size;
 	uint8_t* glyph_buffer;
+	KernelSegment kernel_segments[MAX_KERNEL_SEGMENTS];
+	uint64_t kernel_segment_count;
+	void* rsdp;
+	EFI_RUNTIME_SERVICES* runtime_services;
+	Elf64_Sym* symbol_table;
+	uint64_t symbol_table_size;
+	char* string_table;
+	uint64_t string_table_size;
 } BootInfo;
 
+#define PAGE_PRESENT 0x1
+#define PAGE_WRITABLE 0x2
+#define PAGE_ADDRESS_MASK 0x000FFFFFFFFFF000
+
+//where the kernel maps all of physical memory, runtime services are moved here
+#define PHYSMAP_OFFSET 0xFFFF800000000000
+
+//finds the ACPI RSDP in the EFI configuration table, preferring the ACPI 2.0 one, NULL if there is none
+void* find_rsdp() {
+	void* rsdp = NULL;
+	for (UINTN i = 0; i < ST->NumberOfTableEntries; ++i) {
+		EFI_CONFIGURATION_TABLE* table = &ST->ConfigurationTable[i];
+		if (CompareGuid(&table->VendorGuid, &Acpi20TableGuid) == 0) {
+			return table->VendorTable;
+		}
+		if (CompareGuid(&table->VendorGuid, &AcpiTableGuid) == 0) {
+			rsdp = table->VendorTable;
+		}
+	}
+	return rsdp;
+}
+
+//moves runtime services into the kernel's physmap, they can only be called once the kernel's page tables are loaded
+EFI_STATUS set_virtual_address_map(EFI_MEMORY_DESCRIPTOR* memory_map, UINTN memory_map_size, UINTN descriptor_size, UINT32 descriptor_version) {
+	for (UINTN offset = 0; offset < memory_map_size; offset += descriptor_size) {
+		EFI_MEMORY_DESCRIPTOR* descriptor = (EFI_MEMORY_DESCRIPTOR*)((uint8_t*)memory_map + offset);
+		if (descriptor->Attribute & EFI_MEMORY_RUNTIME) {
+			descriptor->VirtualStart = descriptor->PhysicalStart + PHYSMAP_OFFSET;
+		}
+	}
+	return uefi_call_wrapper(RT->SetVirtualAddressMap, 4, memory_map_size, descriptor_size, descriptor_version, memory_map);
+}
+
+//allocates a zeroed page table
+uint64_t* alloc_table() {
+	EFI_PHYSICAL_ADDRESS table;
+	uefi_call_wrapper(BS->AllocatePages, 4, AllocateAnyPages, EfiLoaderData, 1, &table);
+	ZeroMem((void*)table, 0x1000);
+	return (uint64_t*)table;
+}
+
+//returns the table the entry at index points to, creating it if it isn't present
+uint64_t* next_table(uint64_t* table, uint64_t index) {
+	if (!(table[index] & PAGE_PRESENT)) {
+		table[index] = (uint64_t)alloc_table() | PAGE_PRESENT | PAGE_WRITABLE;
+	}
+	return (uint64_t*)(table[index] & PAGE_ADDRESS_MASK);
+}
+
+void map_page(uint64_t* pml4, uint64_t virtual_address, uint64_t physical_address) {
+	uint64_t* pdpt = next_table(pml4, (virtual_address >> 39) & 0x1FF);
+	uint64_t* pd = next_table(pdpt, (virtual_address >> 30) & 0x1FF);
+	uint64_t* pt = next_table(pd, (virtual_address >> 21) & 0x1FF);
+	pt[(virtual_address >> 12) & 0x1FF] = physical_address | PAGE_PRESENT | PAGE_WRITABLE;
+}
+
+//creates a pml4 that keeps the firmware's identity mapped lower half and maps the kernel segments in the higher half
+uint64_t* create_kernel_pml4(BootInfo* boot_info) {
+	uint64_t cr3;
+	__asm__ volatile("mov %%cr3, %0" : "=r"(cr3));
+	uint64_t* firmware_pml4 = (uint64_t*)(cr3 & PAGE_ADDRESS_MASK);
+
+	uint64_t* pml4 = alloc_table();
+	for (int i = 0; i < 256; ++i) {
+		pml4[i] = firmware_pml4[i];
+	}
+
+	for (uint64_t i = 0; i < boot_info->kernel_segment_count; ++i) {
+		KernelSegment* segment = &boot_info->kernel_segments[i];
+		for (uint64_t offset = 0; offset < segment->size; offset += 0x1000) {
+			map_page(pml4, segment->virtual_start + offset, segment->physical_start + offset);
+		}
+	}
+
+	return pml4;
+}
+
 //returns the file handle to the volume that the efi file is in
 EFI_FILE_HANDLE get_volume(EFI_HANDLE image) {
 	EFI_LOADED_IMAGE *loaded_image = NULL;
@@ -73,6 +168,69 @@ uint8_t* load_font(EFI_FILE_HANDLE font) {
 	return glyph_buffer;
 }
 
+//reads a whole section of the kernel into pool memory, NULL if it couldn't be read
+void* load_section(EFI_FILE_HANDLE kernel, Elf64_Shdr* section) {
+	void* data = NULL;
+	if (EFI_ERROR(uefi_call_wrapper(BS->AllocatePool, 3, EfiLoaderData, section->sh_size, &data))) {
+		return NULL;
+	}
+
+	UINTN size = section->sh_size;
+	uefi_call_wrapper(kernel->SetPosition, 2, kernel, section->sh_offset);
+	if (EFI_ERROR(uefi_call_wrapper(kernel->Read, 3, kernel, &size, data)) || size != section->sh_size) {
+		uefi_call_wrapper(BS->FreePool, 1, data);
+		return NULL;
+	}
+	return data;
+}
+
+//reads section header index, returns 0 if there is no such section
+int read_section_header(EFI_FILE_HANDLE kernel, Elf64_Ehdr* ehdr, uint64_t index, Elf64_Shdr* section) {
+	if (index >= ehdr->e_shnum || ehdr->e_shentsize != sizeof(Elf64_Shdr)) {
+		return 0;
+	}
+
+	UINTN size = sizeof(Elf64_Shdr);
+	uefi_call_wrapper(kernel->SetPosition, 2, kernel, ehdr->e_shoff + index * sizeof(Elf64_Shdr));
+	uefi_call_wrapper(kernel->Read, 3, kernel, &size, section);
+	return size == sizeof(Elf64_Shdr);
+}
+
+//loads the kernel's symbol table and its string table so faults can be reported by function, left NULL if the
+//kernel was stripped
+void load_symbols(EFI_FILE_HANDLE kernel, Elf64_Ehdr* ehdr, BootInfo* boot_info) {
+	boot_info->symbol_table = NULL;
+	boot_info->symbol_table_size = 0;
+	boot_info->string_table = NULL;
+	boot_info->string_table_size = 0;
+
+	for (uint16_t i = 0; i < ehdr->e_shnum; ++i) {
+		Elf64_Shdr symbols;
+		if (!read_section_header(kernel, ehdr, i, &symbols) || symbols.sh_type != SHT_SYMTAB) {
+			continue;
+		}
+
+		//sh_link is the index of the string table the symbol names point into
+		Elf64_Shdr strings;
+		if (!read_section_header(kernel, ehdr, symbols.sh_link, &strings) || strings.sh_type != SHT_STRTAB) {
+			return;
+		}
+
+		Elf64_Sym* symbol_table = load_section(kernel, &symbols);
+		char* string_table = load_section(kernel, &strings);
+		if (symbol_table == NULL || string_table == NULL) {
+			Print(L"kernel symbols couldn't be loaded\n");
+			return;
+		}
+
+		boot_info->symbol_table = symbol_table;
+		boot_info->symbol_table_size = symbols.sh_size;
+		boot_info->string_table = string_table;
+		boot_info->string_table_size = strings.sh_size;
+		return;
+	}
+}
+
 EFI_STATUS EFIAPI efi_main(EFI_HANDLE image_handle, EFI_SYSTEM_TABLE *system_table) {
 	InitializeLib(image_handle, system_table);
 
@@ -106,6 +264,9 @@ EFI_STATUS EFIAPI efi_main(EFI_HANDLE image_handle, EFI_SYSTEM_TABLE *system_tab
 		return EFI_LOAD_ERROR;
 	}
 
+	BootInfo boot_info;
+	boot_info.kernel_segment_count = 0;
+
 	//read program headers
 	uint64_t offset = ehdr.e_phoff;
 	for (uint16_t i = 0; i < ehdr.e_phnum; ++i) {
@@ -117,21 +278,40 @@ EFI_STATUS EFIAPI efi_main(EFI_HANDLE image_handle, EFI_SYSTEM_TABLE *system_tab
 
 		//if the program header says the data is loadable we load it
 		if (phdr.p_type == PT_LOAD) {
-			//allocate memory for program data
-			int pages = (phdr.p_memsz + 0x1000 - 1) / 0x1000;
-			Elf64_Addr segment = phdr.p_paddr;
-			uefi_call_wrapper(BS->AllocatePages, 4, AllocateAddress, EfiLoaderData, pages, &segment);
+			if (boot_info.kernel_segment_count == MAX_KERNEL_SEGMENTS) {
+				Print(L"kernel has too many segments\n");
+				return EFI_LOAD_ERROR;
+			}
+
+			//allocate zeroed memory anywhere for program data, the kernel is mapped to p_vaddr later
+			uint64_t page_offset = phdr.p_vaddr & 0xFFF;
+			int pages = (page_offset + phdr.p_memsz + 0x1000 - 1) / 0x1000;
+			Elf64_Addr segment;
+			uefi_call_wrapper(BS->AllocatePages, 4, AllocateAnyPages, EfiLoaderData, pages, &segment);
+			ZeroMem((void*)segment, pages * 0x1000);
 
 			//write program data into memory
 			uefi_call_wrapper(kernel->SetPosition, 2, kernel, phdr.p_offset);
 			UINTN size = phdr.p_filesz;
-			uefi_call_wrapper(kernel->Read, 3, kernel, &size, (void*)segment);
+			uefi_call_wrapper(kernel->Read, 3, kernel, &size, (void*)(segment + page_offset));
+
+			//record where the segment went
+			KernelSegment* kernel_segment = &boot_info.kernel_segments[boot_info.kernel_segment_count++];
+			kernel_segment->physical_start = segment;
+			kernel_segment->virtual_start = phdr.p_vaddr - page_offset;
+			kernel_segment->size = pages * 0x1000;
+			kernel_segment->flags = phdr.p_flags;
 		}
 
 		//point to next program header
 		offset += size;
 	}
 
+	load_symbols(kernel, &ehdr, &boot_info);
+
+	//page tables are allocated now since boot services are gone by the time they're loaded
+	uint64_t* kernel_pml4 = create_kernel_pml4(&boot_info);
+
 	//get graphics output protocol
 	EFI_GUID gop_guid = EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID;
 	EFI_GRAPHICS_OUTPUT_PROTOCOL *gop;
@@ -160,6 +340,12 @@ EFI_STATUS EFIAPI efi_main(EFI_HANDLE image_handle, EFI_SYSTEM_TABLE *system_tab
 		return EFI_LOAD_ERROR;
 	}
 
+	//find acpi tables, the configuration table can't be used after exiting boot services
+	void* rsdp = find_rsdp();
+	if (rsdp == NULL) {
+		Print(L"ACPI RSDP not found\n");
+	}
+
 	//get memory map
 	UINTN memory_map_size = 0;
 	EFI_MEMORY_DESCRIPTOR* memory_map = NULL;
@@ -173,17 +359,24 @@ EFI_STATUS EFIAPI efi_main(EFI_HANDLE image_handle, EFI_SYSTEM_TABLE *system_tab
 	//exit boot services
 	uefi_call_wrapper(BS->ExitBootServices, 2, image_handle, memory_map_key);
 
-	BootInfo boot_info;
+	//the table itself isn't moved, only the pointers inside it, so the kernel gets its physical address
+	boot_info.runtime_services = RT;
+	if (EFI_ERROR(set_virtual_address_map(memory_map, memory_map_size, descriptor_size, descriptor_version))) {
+		boot_info.runtime_services = NULL;
+	}
+
 	boot_info.frame_buffer = &frame_buffer;
 	boot_info.memory_map = memory_map;
 	boot_info.memory_map_size = memory_map_size;
-	boot_info.descriptor_size = memory_map_size;
+	boot_info.descriptor_size = descriptor_size;
 	boot_info.glyph_buffer = glyph_buffer;
+	boot_info.rsdp = rsdp;
 
 	//define KernelStart function
 	void (*KernelStart)(BootInfo*) = ((__attribute__((sysv_abi)) void(*)(BootInfo*))ehdr.e_entry);
 
-	//execute kernel
+	//switch to the kernel's address space and execute kernel
+	__asm__ volatile("mov %0, %%cr3" : : "r"(kernel_pml4) : "memory");
 	KernelStart(&boot_info);
 	
 	return EFI_SUCCESS;
@@ -0,0 +1,108 @@
+//! # Fixed ACPI Description Table
+//! 
+//! Where the power management registers are and where the DSDT is.
+
+use super::sdt::{read_table, GenericAddress, SdtHeader};
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct FadtTable {
+    header: SdtHeader,
+    firmware_ctrl: u32,
+    dsdt: u32,
+    reserved: u8,
+    preferred_pm_profile: u8,
+    sci_interrupt: u16,
+    smi_command: u32,
+    acpi_enable: u8,
+    acpi_disable: u8,
+    s4bios_request: u8,
+    pstate_control: u8,
+    pm1a_event_block: u32,
+    pm1b_event_block: u32,
+    pm1a_control_block: u32,
+    pm1b_control_block: u32,
+    pm2_control_block: u32,
+    pm_timer_block: u32,
+    gpe0_block: u32,
+    gpe1_block: u32,
+    pm1_event_length: u8,
+    pm1_control_length: u8,
+    pm2_control_length: u8,
+    pm_timer_length: u8,
+    gpe0_length: u8,
+    gpe1_length: u8,
+    gpe1_base: u8,
+    cstate_control: u8,
+    worst_c2_latency: u16,
+    worst_c3_latency: u16,
+    flush_size: u16,
+    flush_stride: u16,
+    duty_offset: u8,
+    duty_width: u8,
+    day_alarm: u8,
+    month_alarm: u8,
+    century: u8,
+    boot_architecture_flags: u16,
+    reserved_2: u8,
+    flags: u32,
+    reset_register: GenericAddress,
+    reset_value: u8,
+    arm_boot_architecture_flags: u16,
+    minor_version: u8,
+    x_firmware_control: u64,
+    x_dsdt: u64,
+    x_pm1a_event_block: GenericAddress,
+    x_pm1b_event_block: GenericAddress,
+    x_pm1a_control_block: GenericAddress,
+    x_pm1b_control_block: GenericAddress,
+}
+
+/// Set in [`Fadt::flags`] when [`Fadt::reset_register`] can be used.
+pub const FLAG_RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+
+/// Set in [`Fadt::boot_architecture_flags`] when there is an 8042 keyboard controller.
+pub const BOOT_ARCH_8042: u16 = 1 << 1;
+
+/// The parts of the FADT the kernel uses, IO port addresses are 0 when a register doesn't exist.
+#[derive(Debug, Clone, Copy)]
+pub struct Fadt {
+    pub dsdt: u64,
+    pub sci_interrupt: u16,
+    pub smi_command: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_event_block: u32,
+    pub pm1a_control_block: u32,
+    pub pm1b_control_block: u32,
+    pub pm_timer_block: u32,
+    /// CMOS register holding the century, 0 if there isn't one.
+    pub century: u8,
+    pub boot_architecture_flags: u16,
+    pub flags: u32,
+    pub reset_register: GenericAddress,
+    pub reset_value: u8,
+}
+
+/// Parses the FADT at a physical address, its checksum must already have been checked.
+pub fn parse(physical_address: u64) -> Fadt {
+    let table: FadtTable = read_table(physical_address);
+    // The 64 bit address wins when both are present, fields past the end of an old table read as 0
+    let dsdt = if table.x_dsdt != 0 { table.x_dsdt } else { table.dsdt as u64 };
+    return Fadt {
+        dsdt: dsdt,
+        sci_interrupt: table.sci_interrupt,
+        smi_command: table.smi_command,
+        acpi_enable: table.acpi_enable,
+        acpi_disable: table.acpi_disable,
+        pm1a_event_block: table.pm1a_event_block,
+        pm1a_control_block: table.pm1a_control_block,
+        pm1b_control_block: table.pm1b_control_block,
+        pm_timer_block: table.pm_timer_block,
+        century: table.century,
+        boot_architecture_flags: table.boot_architecture_flags,
+        flags: table.flags,
+        reset_register: table.reset_register,
+        reset_value: table.reset_value,
+    };
+}
@@ -0,0 +1,36 @@
+//! # High Precision Event Timer table
+
+use super::sdt::{read_physical, GenericAddress, SdtHeader};
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct HpetTable {
+    header: SdtHeader,
+    event_timer_block_id: u32,
+    base_address: GenericAddress,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hpet {
+    /// Physical address of the HPET's registers.
+    pub base_address: u64,
+    pub hpet_number: u8,
+    pub comparators: u8,
+    /// Smallest period in main counter ticks that won't lose interrupts in periodic mode.
+    pub minimum_tick: u16,
+}
+
+/// Parses the HPET table at a physical address, its checksum must already have been checked.
+pub fn parse(physical_address: u64) -> Hpet {
+    let table: HpetTable = read_physical(physical_address);
+    let base = table.base_address;
+    return Hpet {
+        base_address: base.address,
+        hpet_number: table.hpet_number,
+        comparators: (((table.event_timer_block_id >> 8) & 0x1F) + 1) as u8,
+        minimum_tick: table.minimum_tick,
+    };
+}
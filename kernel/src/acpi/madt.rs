@@ -0,0 +1,148 @@
+//! # Multiple APIC Description Table
+//! 
+//! Lists the CPUs, IO-APICs and how ISA interrupts are wired to the IO-APICs.
+
+use super::sdt::{read_physical, SdtHeader};
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct MadtHeader {
+    header: SdtHeader,
+    local_apic_address: u32,
+    flags: u32,
+}
+
+// Entry types
+const PROCESSOR_LOCAL_APIC: u8 = 0;
+const IO_APIC: u8 = 1;
+const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const LOCAL_APIC_NMI: u8 = 4;
+const LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const PROCESSOR_LOCAL_X2APIC: u8 = 9;
+
+const PROCESSOR_ENABLED: u32 = 1 << 0;
+const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+
+/// A CPU's local APIC.
+#[derive(Debug, Clone, Copy)]
+pub struct Processor {
+    pub processor_id: u32,
+    pub apic_id: u32,
+    /// Either running or can be started.
+    pub usable: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicEntry {
+    pub id: u8,
+    pub address: u64,
+    pub gsi_base: u32,
+}
+
+/// An ISA IRQ that isn't wired to the IO-APIC pin with the same number.
+#[derive(Debug, Clone, Copy)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub active_low: bool,
+    pub level_triggered: bool,
+}
+
+/// NMI connection to a local APIC LINT pin, processor 0xFF means every CPU.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalApicNmi {
+    pub processor_id: u8,
+    pub lint: u8,
+    pub active_low: bool,
+    pub level_triggered: bool,
+}
+
+pub struct Madt {
+    pub local_apic_address: u64,
+    pub processors: Vec<Processor>,
+    pub io_apics: Vec<IoApicEntry>,
+    pub overrides: Vec<InterruptOverride>,
+    pub nmis: Vec<LocalApicNmi>,
+}
+
+// MPS INTI flags, 0b11 in the polarity bits is active low and in the trigger bits is level triggered
+fn polarity_and_trigger(flags: u16) -> (bool, bool) {
+    return (flags & 0b11 == 0b11, (flags >> 2) & 0b11 == 0b11);
+}
+
+/// Parses the MADT at a physical address, its checksum must already have been checked.
+pub fn parse(physical_address: u64) -> Madt {
+    let madt: MadtHeader = read_physical(physical_address);
+    let mut result = Madt {
+        local_apic_address: madt.local_apic_address as u64,
+        processors: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+        nmis: Vec::new(),
+    };
+
+    let end = physical_address + madt.header.length as u64;
+    let mut entry = physical_address + size_of::<MadtHeader>() as u64;
+    while entry + 2 <= end {
+        let entry_type: u8 = read_physical(entry);
+        let length: u8 = read_physical(entry + 1);
+        if length < 2 {
+            break;
+        }
+
+        match entry_type {
+            PROCESSOR_LOCAL_APIC => {
+                let processor_id: u8 = read_physical(entry + 2);
+                let apic_id: u8 = read_physical(entry + 3);
+                let flags: u32 = read_physical(entry + 4);
+                result.processors.push(Processor {
+                    processor_id: processor_id as u32,
+                    apic_id: apic_id as u32,
+                    usable: flags & (PROCESSOR_ENABLED | PROCESSOR_ONLINE_CAPABLE) != 0,
+                });
+            },
+            IO_APIC => {
+                let address: u32 = read_physical(entry + 4);
+                result.io_apics.push(IoApicEntry {
+                    id: read_physical(entry + 2),
+                    address: address as u64,
+                    gsi_base: read_physical(entry + 8),
+                });
+            },
+            INTERRUPT_SOURCE_OVERRIDE => {
+                let (active_low, level_triggered) = polarity_and_trigger(read_physical(entry + 8));
+                result.overrides.push(InterruptOverride {
+                    irq: read_physical(entry + 3),
+                    gsi: read_physical(entry + 4),
+                    active_low: active_low,
+                    level_triggered: level_triggered,
+                });
+            },
+            LOCAL_APIC_NMI => {
+                let (active_low, level_triggered) = polarity_and_trigger(read_physical(entry + 3));
+                result.nmis.push(LocalApicNmi {
+                    processor_id: read_physical(entry + 2),
+                    lint: read_physical(entry + 5),
+                    active_low: active_low,
+                    level_triggered: level_triggered,
+                });
+            },
+            LOCAL_APIC_ADDRESS_OVERRIDE => {
+                result.local_apic_address = read_physical(entry + 4);
+            },
+            PROCESSOR_LOCAL_X2APIC => {
+                let flags: u32 = read_physical(entry + 8);
+                result.processors.push(Processor {
+                    processor_id: read_physical(entry + 12),
+                    apic_id: read_physical(entry + 4),
+                    usable: flags & (PROCESSOR_ENABLED | PROCESSOR_ONLINE_CAPABLE) != 0,
+                });
+            },
+            _ => {},
+        }
+        entry += length as u64;
+    }
+    return result;
+}
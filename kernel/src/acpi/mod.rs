@@ -0,0 +1,146 @@
+//! # ACPI table discovery
+//! 
+//! Finds the system description tables from the RSDP the bootloader passes in, checks their checksums and parses the
+//! ones the kernel uses. The tables stay in firmware memory, which is never reclaimed, and are read through the
+//! physmap.
+//! 
+//! [`init`]
+//! 
+//! [`ACPI`] everything that was found
+//! 
+//! [`madt`] CPUs, IO-APICs and interrupt overrides
+//! 
+//! [`fadt`] power management registers
+//! 
+//! [`hpet`] high precision event timer
+
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+pub mod sdt;
+
+use crate::{print, println};
+use alloc::vec::Vec;
+use core::mem::size_of;
+use fadt::Fadt;
+use hpet::Hpet;
+use madt::Madt;
+use sdt::{checksum_valid, read_physical, Rsdp, SdtHeader, RSDP_V1_SIZE};
+use crate::sync::IrqSpinLock;
+
+/// The ACPI tables found by [`init`].
+pub struct Acpi {
+    /// 0 for ACPI 1.0, 2 or more when there is an XSDT.
+    pub revision: u8,
+    tables: Vec<([u8; 4], u64)>,
+    pub madt: Option<Madt>,
+    pub fadt: Option<Fadt>,
+    pub hpet: Option<Hpet>,
+}
+
+pub static ACPI: IrqSpinLock<Acpi> = IrqSpinLock::new(Acpi::new());
+
+impl Acpi {
+    const fn new() -> Acpi {
+        Acpi {
+            revision: 0,
+            tables: Vec::new(),
+            madt: None,
+            fadt: None,
+            hpet: None,
+        }
+    }
+
+    /// Physical address of the first valid table with the signature.
+    pub fn find_table(&self, signature: &[u8; 4]) -> Option<u64> {
+        self.tables.iter().find(|(s, _)| s == signature).map(|&(_, address)| address)
+    }
+}
+
+/// # Init
+/// 
+/// Walks the XSDT, or the RSDT on ACPI 1.0 machines, and parses the MADT, FADT and HPET tables into [`ACPI`]. Tables
+/// with a bad checksum are skipped.
+/// 
+/// ## Arguments
+/// * 'rsdp_address' - physical address of the RSDP, 0 if the firmware didn't have one
+pub fn init(rsdp_address: u64) -> () {
+    if rsdp_address == 0 {
+        println!(0x00FF2222; "No ACPI tables");
+        return;
+    }
+
+    let rsdp: Rsdp = read_physical(rsdp_address);
+    if &rsdp.signature != b"RSD PTR " || !checksum_valid(rsdp_address, RSDP_V1_SIZE) {
+        println!(0x00FF2222; "Invalid ACPI RSDP at {:#x}", rsdp_address);
+        return;
+    }
+    let use_xsdt = rsdp.revision >= 2 && rsdp.xsdt_address != 0
+        && checksum_valid(rsdp_address, rsdp.length as usize);
+
+    let (root, entry_size) = if use_xsdt { (rsdp.xsdt_address, 8) } else { (rsdp.rsdt_address as u64, 4) };
+    let root_header: SdtHeader = read_physical(root);
+    if !checksum_valid(root, root_header.length as usize) {
+        println!(0x00FF2222; "Invalid ACPI root table at {:#x}", root);
+        return;
+    }
+
+    let mut acpi = ACPI.lock();
+    acpi.revision = rsdp.revision;
+
+    let entries = (root_header.length as u64 - size_of::<SdtHeader>() as u64) / entry_size;
+    for i in 0..entries {
+        let entry = root + size_of::<SdtHeader>() as u64 + i * entry_size;
+        let address = if entry_size == 8 { read_physical::<u64>(entry) } else { read_physical::<u32>(entry) as u64 };
+        add_table(&mut acpi, address);
+    }
+
+    if let Some(address) = acpi.find_table(b"APIC") {
+        acpi.madt = Some(madt::parse(address));
+    }
+    if let Some(address) = acpi.find_table(b"FACP") {
+        let fadt = fadt::parse(address);
+        // The DSDT isn't listed in the root table, only in the FADT
+        add_table(&mut acpi, fadt.dsdt);
+        acpi.fadt = Some(fadt);
+    }
+    if let Some(address) = acpi.find_table(b"HPET") {
+        acpi.hpet = Some(hpet::parse(address));
+    }
+
+    print_summary(&acpi);
+}
+
+// Records a table if its checksum is valid.
+fn add_table(acpi: &mut Acpi, address: u64) -> () {
+    if address == 0 {
+        return;
+    }
+    let header: SdtHeader = read_physical(address);
+    if !checksum_valid(address, header.length as usize) {
+        println!(0x00FF2222; "Skipping ACPI table {} with a bad checksum", signature_str(&header.signature));
+        return;
+    }
+    acpi.tables.push((header.signature, address));
+}
+
+fn signature_str(signature: &[u8; 4]) -> &str {
+    core::str::from_utf8(signature).unwrap_or("????")
+}
+
+fn print_summary(acpi: &Acpi) -> () {
+    print!(0x0022FF22; "-- Found ACPI {} tables:", if acpi.revision >= 2 { "2.0" } else { "1.0" });
+    for (signature, _) in acpi.tables.iter() {
+        print!(0x0022FF22; " {}", signature_str(signature));
+    }
+    println!();
+
+    if let Some(madt) = &acpi.madt {
+        println!("CPUs: {}, IO-APICs: {}, interrupt overrides: {}",
+            madt.processors.iter().filter(|p| p.usable).count(), madt.io_apics.len(), madt.overrides.len());
+    }
+    if let Some(hpet) = &acpi.hpet {
+        let base = hpet.base_address;
+        println!("HPET at {:#x} with {} comparators", base, hpet.comparators);
+    }
+}
@@ -0,0 +1,85 @@
+//! # Common ACPI structures
+//! 
+//! [`Rsdp`] root system description pointer, found through the EFI configuration table
+//! 
+//! [`SdtHeader`] header every system description table starts with
+//! 
+//! [`GenericAddress`] location of a register in memory or IO space
+
+use crate::paging::phys_to_virt;
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+    // The rest only exists from ACPI 2.0
+    pub length: u32,
+    pub xsdt_address: u64,
+    pub extended_checksum: u8,
+    pub reserved: [u8; 3],
+}
+
+// Size of the ACPI 1.0 RSDP, which ends after rsdt_address
+pub const RSDP_V1_SIZE: usize = 20;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+pub const ADDRESS_SPACE_MEMORY: u8 = 0;
+pub const ADDRESS_SPACE_IO: u8 = 1;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+/// Returns true if the bytes sum to zero, which is how every ACPI structure is checksummed.
+pub fn checksum_valid(physical_address: u64, length: usize) -> bool {
+    let start = phys_to_virt(physical_address) as *const u8;
+    let mut sum: u8 = 0;
+    for i in 0..length {
+        sum = sum.wrapping_add(unsafe { *start.add(i) });
+    }
+    return sum == 0;
+}
+
+/// Reads a structure from physical memory, ACPI tables have no alignment guarantees.
+pub fn read_physical<T: Copy>(physical_address: u64) -> T {
+    unsafe { read_unaligned(phys_to_virt(physical_address) as *const T) }
+}
+
+/// # Read table
+/// 
+/// Reads a table that may be shorter than `T`, older revisions of a table leave off fields from the end. The missing
+/// fields read as zero.
+pub fn read_table<T: Copy>(physical_address: u64) -> T {
+    let header: SdtHeader = read_physical(physical_address);
+    let length = (header.length as usize).min(size_of::<T>());
+    unsafe {
+        let mut table: T = core::mem::zeroed();
+        core::ptr::copy_nonoverlapping(phys_to_virt(physical_address) as *const u8, &mut table as *mut T as *mut u8, length);
+        return table;
+    }
+}
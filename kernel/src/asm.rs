@@ -183,6 +183,17 @@ pub fn nop() -> () {
     }
 }
 
+//reads the time stamp counter, cycles since reset
+#[inline(always)]
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!("rdtsc", out("eax") low, out("edx") high);
+    }
+    return (high as u64) << 32 | low as u64;
+}
+
 //cpu halt stops cpu execution
 #[inline(always)]
 pub fn hlt() -> () {
@@ -213,4 +224,30 @@ pub fn cli() -> () {
     unsafe {
         asm!("cli");
     }
-}
\ No newline at end of file
+}
+
+//sets interrupts
+#[inline(always)]
+pub fn sti() -> () {
+    unsafe {
+        asm!("sti");
+    }
+}
+
+//reads the flags register, bit 9 is the interrupt flag
+#[inline(always)]
+pub fn read_rflags() -> u64 {
+    let x: u64;
+    unsafe {
+        asm!("pushfq", "pop {0}", out(reg) x);
+    }
+    return x;
+}
+
+//invalidates the tlb entry for the page containing addr
+#[inline(always)]
+pub fn invlpg(addr: u64) -> () {
+    unsafe {
+        asm!("invlpg [{0}]", in(reg) addr);
+    }
+}
@@ -0,0 +1,90 @@
+//! # HPET main counter
+//!
+//! Only the free running main counter is used, the comparators are left off.
+//! https://wiki.osdev.org/HPET
+
+use crate::paging::map_mmio;
+use kernel_lib::time::femtos_to_nanos;
+
+const REGISTERS_SIZE: u64 = 0x400;
+
+const GENERAL_CAPABILITIES: u64 = 0x00;
+const GENERAL_CONFIGURATION: u64 = 0x10;
+const MAIN_COUNTER: u64 = 0xF0;
+
+const CAPABILITY_64_BIT: u64 = 1 << 13;
+const CONFIGURATION_ENABLE: u64 = 1 << 0;
+
+// The spec limits the period to 100 ns
+const MAX_PERIOD_FEMTOS: u64 = 100_000_000;
+
+pub struct Hpet {
+    registers: u64,
+    /// Length of a main counter tick in femtoseconds.
+    pub period_femtos: u64,
+    /// A 32 bit counter wraps every few minutes, too quickly to be a clock source.
+    pub is_64_bit: bool,
+}
+
+impl Hpet {
+    /// # Init
+    ///
+    /// Maps the registers and starts the main counter.
+    ///
+    /// ## Arguments
+    /// * 'physical_address' - base of the registers from the ACPI HPET table
+    ///
+    /// ## Returns
+    /// * 'Option<Hpet>' - None if the registers report an impossible period
+    pub fn init(physical_address: u64) -> Option<Hpet> {
+        let registers = map_mmio(physical_address, REGISTERS_SIZE);
+        let mut hpet = Hpet {
+            registers: registers,
+            period_femtos: 0,
+            is_64_bit: false,
+        };
+
+        let capabilities = hpet.read(GENERAL_CAPABILITIES);
+        hpet.period_femtos = capabilities >> 32;
+        hpet.is_64_bit = capabilities & CAPABILITY_64_BIT != 0;
+        if hpet.period_femtos == 0 || hpet.period_femtos > MAX_PERIOD_FEMTOS {
+            return None;
+        }
+
+        let configuration = hpet.read(GENERAL_CONFIGURATION);
+        hpet.write(GENERAL_CONFIGURATION, configuration | CONFIGURATION_ENABLE);
+        return Some(hpet);
+    }
+
+    /// Current value of the main counter.
+    pub fn counter(&self) -> u64 {
+        self.read(MAIN_COUNTER)
+    }
+
+    /// Ticks from `start` to `end`, two main counter readings, taking a wrap of a 32 bit counter into account.
+    pub fn ticks_between(&self, start: u64, end: u64) -> u64 {
+        let ticks = end.wrapping_sub(start);
+        match self.is_64_bit {
+            true => ticks,
+            false => ticks & u32::MAX as u64,
+        }
+    }
+
+    /// Main counter ticks per second.
+    pub fn frequency(&self) -> u64 {
+        1_000_000_000_000_000 / self.period_femtos
+    }
+
+    /// Converts main counter ticks to nanoseconds.
+    pub fn ticks_to_nanos(&self, ticks: u64) -> u64 {
+        femtos_to_nanos(ticks, self.period_femtos)
+    }
+
+    fn read(&self, register: u64) -> u64 {
+        unsafe { core::ptr::read_volatile((self.registers + register) as *const u64) }
+    }
+
+    fn write(&self, register: u64, value: u64) -> () {
+        unsafe { core::ptr::write_volatile((self.registers + register) as *mut u64, value) }
+    }
+}
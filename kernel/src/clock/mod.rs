@@ -0,0 +1,150 @@
+//! # Clock
+//!
+//! Monotonic time from the best source the machine has, in order of preference an invariant TSC, the HPET's main
+//! counter, then PIT ticks.
+//!
+//! [`init`] picks and calibrates the source
+//!
+//! [`Instant`] a point in time with nanosecond resolution, subtracting two gives a [`Duration`]
+//!
+//! [`source`] / [`uptime`]
+//!
+//! [`hpet`] / [`tsc`] the counters behind the faster sources
+
+pub mod hpet;
+pub mod tsc;
+
+use crate::{acpi, asm, println};
+use crate::io::pit;
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+pub use core::time::Duration;
+use hpet::Hpet;
+use kernel_lib::time::ticks_to_nanos;
+use spin::Once;
+
+/// Where [`Instant::now`] gets its time from.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    /// PIT ticks, resolution is a single tick and time stands still with interrupts off
+    Pit = 0,
+    Hpet = 1,
+    Tsc = 2,
+}
+
+static SOURCE: AtomicU8 = AtomicU8::new(ClockSource::Pit as u8);
+static TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+static TSC_START: AtomicU64 = AtomicU64::new(0);
+static HPET: Once<Hpet> = Once::new();
+static HPET_START: AtomicU64 = AtomicU64::new(0);
+
+/// # Init
+///
+/// Starts the HPET if ACPI found one and picks the clock source. The TSC is calibrated against the HPET when there is
+/// one, otherwise the PIT. Falls back to PIT ticks, so [`crate::io::init_pit`] must have been called first.
+pub fn init() -> () {
+    let hpet_address = acpi::ACPI.lock().hpet.map(|hpet| hpet.base_address);
+    let hpet = hpet_address.and_then(Hpet::init).map(|hpet| HPET.call_once(|| hpet));
+
+    if tsc::is_supported() && tsc::is_invariant() {
+        let frequency = match hpet {
+            Some(hpet) => tsc::calibrate_with_hpet(hpet),
+            None => tsc::calibrate_with_pit(),
+        };
+        TSC_FREQUENCY.store(frequency, Ordering::Relaxed);
+        TSC_START.store(asm::rdtsc(), Ordering::Relaxed);
+        SOURCE.store(ClockSource::Tsc as u8, Ordering::Relaxed);
+        println!(0x0022FF22; "-- Clock source: invariant TSC at {} MHz, calibrated against the {}",
+            frequency / 1_000_000, if hpet.is_some() { "HPET" } else { "PIT" });
+    } else if let Some(hpet) = hpet.filter(|hpet| hpet.is_64_bit) {
+        HPET_START.store(hpet.counter(), Ordering::Relaxed);
+        SOURCE.store(ClockSource::Hpet as u8, Ordering::Relaxed);
+        println!(0x0022FF22; "-- Clock source: HPET at {} MHz", hpet.frequency() / 1_000_000);
+    } else {
+        SOURCE.store(ClockSource::Pit as u8, Ordering::Relaxed);
+        println!(0x0022FF22; "-- Clock source: PIT ticks at {} Hz", pit::frequency());
+    }
+}
+
+/// The source chosen by [`init`].
+pub fn source() -> ClockSource {
+    match SOURCE.load(Ordering::Relaxed) {
+        2 => ClockSource::Tsc,
+        1 => ClockSource::Hpet,
+        _ => ClockSource::Pit,
+    }
+}
+
+/// Time since the clock source started counting.
+pub fn uptime() -> Duration {
+    Duration::from_nanos(nanos())
+}
+
+// Nanoseconds since the current source started.
+fn nanos() -> u64 {
+    match source() {
+        ClockSource::Tsc => {
+            let ticks = asm::rdtsc() - TSC_START.load(Ordering::Relaxed);
+            ticks_to_nanos(ticks, TSC_FREQUENCY.load(Ordering::Relaxed))
+        },
+        ClockSource::Hpet => match HPET.get() {
+            Some(hpet) => hpet.ticks_to_nanos(hpet.counter() - HPET_START.load(Ordering::Relaxed)),
+            None => 0,
+        },
+        ClockSource::Pit => ticks_to_nanos(pit::ticks(), pit::frequency() as u64),
+    }
+}
+
+/// # Instant
+///
+/// A reading of the monotonic clock, only meaningful compared to other instants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    nanos: u64,
+}
+
+impl Instant {
+    pub fn now() -> Instant {
+        Instant { nanos: nanos() }
+    }
+
+    /// Time from `earlier` to this instant, zero if `earlier` is actually later.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(earlier.nanos))
+    }
+
+    /// Time since this instant was taken.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    /// Nanoseconds since the clock source started counting.
+    pub fn as_nanos(&self) -> u64 {
+        self.nanos
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant { nanos: self.nanos + duration.as_nanos() as u64 }
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        Instant { nanos: self.nanos.saturating_sub(duration.as_nanos() as u64) }
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
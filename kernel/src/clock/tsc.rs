@@ -0,0 +1,62 @@
+//! # Time stamp counter
+//!
+//! The TSC counts at a fixed rate on CPUs with an invariant TSC, which makes it the cheapest clock to read. Its
+//! frequency isn't reported reliably so it is measured against the HPET or the PIT.
+
+use super::hpet::Hpet;
+use crate::asm;
+use crate::io::pit;
+
+// Long enough that the reference clock's resolution doesn't matter, short enough for the PIT's 16 bit count
+const CALIBRATION_US: u64 = 10_000;
+
+/// Returns true if the CPU has a TSC, cpuid leaf 1 edx bit 4.
+pub fn is_supported() -> bool {
+    let (mut eax, mut ebx, mut ecx, mut edx) = (1u32, 0u32, 0u32, 0u32);
+    asm::cpuid(&mut eax, &mut ebx, &mut ecx, &mut edx);
+    return edx & (1 << 4) != 0;
+}
+
+/// Returns true if the TSC keeps counting at the same rate through frequency and sleep state changes, cpuid leaf
+/// 0x80000007 edx bit 8.
+pub fn is_invariant() -> bool {
+    let (mut eax, mut ebx, mut ecx, mut edx) = (0x80000000u32, 0u32, 0u32, 0u32);
+    asm::cpuid(&mut eax, &mut ebx, &mut ecx, &mut edx);
+    if eax < 0x80000007 {
+        return false;
+    }
+
+    let (mut eax, mut ebx, mut ecx, mut edx) = (0x80000007u32, 0u32, 0u32, 0u32);
+    asm::cpuid(&mut eax, &mut ebx, &mut ecx, &mut edx);
+    return edx & (1 << 8) != 0;
+}
+
+/// # Calibrate with HPET
+///
+/// ## Returns
+/// * 'u64' - The TSC frequency in Hz, measured over 10 ms of the HPET's main counter
+pub fn calibrate_with_hpet(hpet: &Hpet) -> u64 {
+    let target_ticks = CALIBRATION_US * hpet.frequency() / 1_000_000;
+    let hpet_start = hpet.counter();
+    let tsc_start = asm::rdtsc();
+    let mut hpet_end = hpet_start;
+    while hpet.ticks_between(hpet_start, hpet_end) < target_ticks {
+        hpet_end = hpet.counter();
+    }
+    let tsc_end = asm::rdtsc();
+    let elapsed_nanos = hpet.ticks_to_nanos(hpet.ticks_between(hpet_start, hpet_end));
+
+    return (tsc_end - tsc_start) * 1_000_000_000 / elapsed_nanos;
+}
+
+/// # Calibrate with PIT
+///
+/// ## Returns
+/// * 'u64' - The TSC frequency in Hz, measured over a 10 ms wait on PIT channel 2
+pub fn calibrate_with_pit() -> u64 {
+    let tsc_start = asm::rdtsc();
+    pit::busy_wait_us(CALIBRATION_US as u32);
+    let tsc_end = asm::rdtsc();
+
+    return (tsc_end - tsc_start) * (1_000_000 / CALIBRATION_US);
+}
@@ -0,0 +1,279 @@
+//! # Typed UEFI memory map
+//! 
+//! [`MemoryType`] every EFI memory type
+//! 
+//! [`MemoryRegion`] a run of pages of one type
+//! 
+//! [`MemoryMap`] the firmware's map sorted by address with adjacent regions of the same type merged
+//! 
+//! [`MEMORY_MAP`] the kernel's copy of the map, filled in by [`init_memory_map`]
+
+use super::{BootInfo, EFI_MEMORY_DESCRIPTOR};
+use crate::println;
+use crate::sync::IrqSpinLock;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// `EFI_MEMORY_RUNTIME`, the region is used by runtime services and has a virtual address after SetVirtualAddressMap.
+pub const ATTRIBUTE_RUNTIME: u64 = 1 << 63;
+const MAX_REGIONS: usize = 512;
+
+/// The type of an EFI memory region, `EFI_MEMORY_TYPE` in the spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryType {
+    Reserved,
+    LoaderCode,
+    LoaderData,
+    BootServicesCode,
+    BootServicesData,
+    RuntimeServicesCode,
+    RuntimeServicesData,
+    Conventional,
+    Unusable,
+    AcpiReclaim,
+    AcpiNvs,
+    MemoryMappedIo,
+    MemoryMappedIoPortSpace,
+    PalCode,
+    Persistent,
+    Unaccepted,
+    Unknown(u32),
+}
+
+impl MemoryType {
+    /// Converts the `type` field of an [`EFI_MEMORY_DESCRIPTOR`].
+    pub fn from_raw(raw: u32) -> MemoryType {
+        match raw {
+            0 => MemoryType::Reserved,
+            1 => MemoryType::LoaderCode,
+            2 => MemoryType::LoaderData,
+            3 => MemoryType::BootServicesCode,
+            4 => MemoryType::BootServicesData,
+            5 => MemoryType::RuntimeServicesCode,
+            6 => MemoryType::RuntimeServicesData,
+            7 => MemoryType::Conventional,
+            8 => MemoryType::Unusable,
+            9 => MemoryType::AcpiReclaim,
+            10 => MemoryType::AcpiNvs,
+            11 => MemoryType::MemoryMappedIo,
+            12 => MemoryType::MemoryMappedIoPortSpace,
+            13 => MemoryType::PalCode,
+            14 => MemoryType::Persistent,
+            15 => MemoryType::Unaccepted,
+            _ => MemoryType::Unknown(raw),
+        }
+    }
+
+    /// Free for the kernel to use straight away.
+    pub fn is_usable(&self) -> bool {
+        *self == MemoryType::Conventional
+    }
+
+    /// Only used by the firmware and bootloader before handoff, free once the kernel has copied what it needs.
+    pub fn is_reclaimable(&self) -> bool {
+        match self {
+            MemoryType::LoaderCode
+            | MemoryType::LoaderData
+            | MemoryType::BootServicesCode
+            | MemoryType::BootServicesData => true,
+            _ => false,
+        }
+    }
+
+    /// Device memory rather than RAM.
+    pub fn is_mmio(&self) -> bool {
+        *self == MemoryType::MemoryMappedIo || *self == MemoryType::MemoryMappedIoPortSpace
+    }
+
+    /// Must stay mapped for UEFI runtime services to work.
+    pub fn is_runtime(&self) -> bool {
+        *self == MemoryType::RuntimeServicesCode || *self == MemoryType::RuntimeServicesData
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryType::Reserved => "Reserved",
+            MemoryType::LoaderCode => "Loader code",
+            MemoryType::LoaderData => "Loader data",
+            MemoryType::BootServicesCode => "Boot services code",
+            MemoryType::BootServicesData => "Boot services data",
+            MemoryType::RuntimeServicesCode => "Runtime services code",
+            MemoryType::RuntimeServicesData => "Runtime services data",
+            MemoryType::Conventional => "Conventional",
+            MemoryType::Unusable => "Unusable",
+            MemoryType::AcpiReclaim => "ACPI reclaim",
+            MemoryType::AcpiNvs => "ACPI NVS",
+            MemoryType::MemoryMappedIo => "MMIO",
+            MemoryType::MemoryMappedIoPortSpace => "MMIO port space",
+            MemoryType::PalCode => "PAL code",
+            MemoryType::Persistent => "Persistent",
+            MemoryType::Unaccepted => "Unaccepted",
+            MemoryType::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+/// # MemoryRegion
+/// 
+/// `pages` 4 KiB pages of physical memory starting at `start`.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub pages: u64,
+    pub memory_type: MemoryType,
+    pub attribute: u64,
+}
+
+impl MemoryRegion {
+    const fn empty() -> MemoryRegion {
+        MemoryRegion {
+            start: 0,
+            pages: 0,
+            memory_type: MemoryType::Reserved,
+            attribute: 0,
+        }
+    }
+
+    /// One past the last byte of the region.
+    pub fn end(&self) -> u64 {
+        self.start + self.size()
+    }
+
+    /// Runtime services need the region mapped, this includes MMIO such as the variable store's flash.
+    pub fn is_runtime(&self) -> bool {
+        self.attribute & ATTRIBUTE_RUNTIME != 0
+    }
+
+    /// Size of the region in bytes.
+    pub fn size(&self) -> u64 {
+        self.pages * PAGE_SIZE
+    }
+}
+
+/// # MemoryMap
+/// 
+/// An owned copy of the firmware's memory map. Unlike the raw descriptor array it doesn't depend on bootloader memory,
+/// so it stays valid after that memory is reclaimed.
+pub struct MemoryMap {
+    regions: [MemoryRegion; MAX_REGIONS],
+    count: usize,
+}
+
+/// The kernel's copy of the memory map.
+pub static MEMORY_MAP: IrqSpinLock<MemoryMap> = IrqSpinLock::new(MemoryMap::new());
+
+impl MemoryMap {
+    /// Creates a map with no regions.
+    pub const fn new() -> MemoryMap {
+        MemoryMap {
+            regions: [MemoryRegion::empty(); MAX_REGIONS],
+            count: 0,
+        }
+    }
+
+    /// # From raw
+    /// 
+    /// Reads the descriptor array the firmware returned, sorts it by address and merges neighbouring regions that have
+    /// the same type and attributes.
+    /// 
+    /// ## Arguments
+    /// * 'memory_map' - pointer to the first descriptor
+    /// * 'memory_map_size' - size of the whole map in bytes
+    /// * 'descriptor_size' - size of a single descriptor in bytes, this is not always size_of::<EFI_MEMORY_DESCRIPTOR>()
+    /// 
+    /// ## Safety
+    /// The pointer and sizes must describe a valid map.
+    pub unsafe fn from_raw(memory_map: *const EFI_MEMORY_DESCRIPTOR, memory_map_size: u64, descriptor_size: u64) -> MemoryMap {
+        let mut map = MemoryMap::new();
+        for i in 0..memory_map_size / descriptor_size {
+            let descriptor = &*((memory_map as u64 + i * descriptor_size) as *const EFI_MEMORY_DESCRIPTOR);
+            if descriptor.number_of_pages == 0 {
+                continue;
+            }
+            map.insert_sorted(MemoryRegion {
+                start: descriptor.physical_start,
+                pages: descriptor.number_of_pages,
+                memory_type: MemoryType::from_raw(descriptor.r#type),
+                attribute: descriptor.attribute,
+            });
+        }
+        return map;
+    }
+
+    // Inserts region in address order, merging it with its neighbours when they line up.
+    fn insert_sorted(&mut self, region: MemoryRegion) -> () {
+        let index = self.regions[..self.count].iter().position(|r| r.start > region.start).unwrap_or(self.count);
+        let mergeable = |a: &MemoryRegion, b: &MemoryRegion| {
+            a.end() == b.start && a.memory_type == b.memory_type && a.attribute == b.attribute
+        };
+
+        if index > 0 && mergeable(&self.regions[index - 1], &region) {
+            self.regions[index - 1].pages += region.pages;
+            // The grown region may now reach the one after it
+            if index < self.count && mergeable(&self.regions[index - 1], &self.regions[index]) {
+                self.regions[index - 1].pages += self.regions[index].pages;
+                self.remove(index);
+            }
+            return;
+        }
+        if index < self.count && mergeable(&region, &self.regions[index]) {
+            self.regions[index].start = region.start;
+            self.regions[index].pages += region.pages;
+            return;
+        }
+
+        if self.count == MAX_REGIONS {
+            panic!("Memory map has more than {} regions", MAX_REGIONS);
+        }
+        self.regions.copy_within(index..self.count, index + 1);
+        self.regions[index] = region;
+        self.count += 1;
+    }
+
+    fn remove(&mut self, index: usize) -> () {
+        self.regions.copy_within(index + 1..self.count, index);
+        self.count -= 1;
+    }
+
+    /// The regions, sorted by start address.
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions[..self.count]
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, MemoryRegion> {
+        self.regions().iter()
+    }
+
+    /// End of the highest region of RAM, MMIO is left out since it can sit far above the end of RAM.
+    pub fn memory_end(&self) -> u64 {
+        self.iter().filter(|r| !r.memory_type.is_mmio()).map(|r| r.end()).max().unwrap_or(0)
+    }
+
+    /// Bytes of conventional memory.
+    pub fn usable_memory(&self) -> u64 {
+        self.iter().filter(|r| r.memory_type.is_usable()).map(|r| r.size()).sum()
+    }
+
+    /// Bytes of memory that can be reclaimed after handoff.
+    pub fn reclaimable_memory(&self) -> u64 {
+        self.iter().filter(|r| r.memory_type.is_reclaimable()).map(|r| r.size()).sum()
+    }
+
+    /// Prints the map as a table.
+    pub fn print(&self) -> () {
+        println!("{:<22} {:<18} {:<18} {:>10}", "Type", "Start", "End", "Size");
+        for region in self.iter() {
+            println!("{:<22} {:#018x} {:#018x} {:>6} KiB",
+                region.memory_type.name(), region.start, region.end(), region.size() / 1024);
+        }
+        println!("Usable: {} KiB, reclaimable: {} KiB", self.usable_memory() / 1024, self.reclaimable_memory() / 1024);
+    }
+}
+
+/// Copies the memory map out of the [`BootInfo`] into [`MEMORY_MAP`] and prints it.
+pub fn init_memory_map(boot_info: &BootInfo) -> () {
+    let mut map = MEMORY_MAP.lock();
+    *map = unsafe { MemoryMap::from_raw(boot_info.memory_map, boot_info.memory_map_size, boot_info.descriptor_size) };
+    println!(0x0022FF22; "-- Read memory map, {} regions", map.regions().len());
+    map.print();
+}
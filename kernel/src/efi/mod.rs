@@ -0,0 +1,80 @@
+//! # Bindings for the C efi structs
+//! 
+//! [`EFI_MEMORY_DESCRIPTOR`] 
+//! 
+//! [`Framebuffer`] GOP Framebuffer binding
+//! 
+//! [`KernelSegment`] Where the bootloader placed a kernel segment
+//! 
+//! [`BootInfo`] Boot info struct defined in bootloader
+//! 
+//! [`memory_map`] Typed copy of the EFI memory map
+//! 
+//! [`runtime`] UEFI runtime services
+
+pub mod memory_map;
+pub mod runtime;
+
+#[repr(C)]
+pub struct EFI_MEMORY_DESCRIPTOR {
+    pub r#type: u32,
+    pub pad: u32,
+    pub physical_start: u64,
+    pub virtual_start: u64,
+    pub number_of_pages: u64,
+    pub attribute: u64,
+}
+
+//width means resolution width, pixels_per_scan_line is actually how wide the framebuffer is
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Framebuffer {
+    pub base_address: *mut u32,
+    pub buffer_size: u64,
+    pub width: u32,
+    pub height: u32,
+    pub pixels_per_scan_line: u32,
+}
+
+pub const MAX_KERNEL_SEGMENTS: usize = 16;
+
+pub const SEGMENT_EXECUTABLE: u64 = 0x1;
+pub const SEGMENT_WRITABLE: u64 = 0x2;
+
+//a PT_LOAD segment of the kernel, flags are the elf p_flags, size is a whole number of pages
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct KernelSegment {
+    pub physical_start: u64,
+    pub virtual_start: u64,
+    pub size: u64,
+    pub flags: u64,
+}
+
+//lives on the bootloader's stack, which is freed by paging::reclaim_boot_memory
+#[repr(C)]
+pub struct BootInfo {
+    pub frame_buffer: *const Framebuffer,
+    pub memory_map: *const EFI_MEMORY_DESCRIPTOR,
+    pub memory_map_size: u64,
+    pub descriptor_size: u64,
+    pub glyph_buffer: *const u8,
+    pub kernel_segments: [KernelSegment; MAX_KERNEL_SEGMENTS],
+    pub kernel_segment_count: u64,
+    //physical address of the ACPI RSDP, 0 if the firmware has none
+    pub rsdp: u64,
+    //physical address of the EFI_RUNTIME_SERVICES table, its pointers have been moved into the physmap
+    pub runtime_services: u64,
+    //physical addresses of the kernel's ELF symbol and string tables, 0 if the kernel was stripped
+    pub symbol_table: u64,
+    pub symbol_table_size: u64,
+    pub string_table: u64,
+    pub string_table_size: u64,
+}
+
+impl BootInfo {
+    /// The kernel segments the bootloader actually loaded.
+    pub fn kernel_segments(&self) -> &[KernelSegment] {
+        &self.kernel_segments[..self.kernel_segment_count as usize]
+    }
+}
@@ -0,0 +1,273 @@
+//! # UEFI runtime services
+//!
+//! The firmware services that survive ExitBootServices. The bootloader moves them into the physmap with
+//! SetVirtualAddressMap, so they can only be called once [`crate::paging::init_paging`] has loaded the kernel's tables.
+//!
+//! [`init`] finds the runtime services table
+//!
+//! [`get_time`] wall clock time from the firmware's RTC driver
+//!
+//! [`get_variable`] / [`set_variable`] NVRAM variables
+//!
+//! [`reset_system`] resets or powers off through the firmware
+
+use crate::paging::phys_to_virt;
+use crate::println;
+use alloc::vec::Vec;
+use core::fmt;
+use crate::sync::IrqSpinLock;
+
+// "RUNTSERV"
+const RUNTIME_SERVICES_SIGNATURE: u64 = 0x5652_4553_544E_5552;
+
+const ERROR_BIT: u64 = 1 << 63;
+
+/// The variable survives a reset.
+pub const VARIABLE_NON_VOLATILE: u32 = 0x1;
+/// The variable is visible before ExitBootServices.
+pub const VARIABLE_BOOTSERVICE_ACCESS: u32 = 0x2;
+/// The variable is visible after ExitBootServices, needed for the kernel to read it back.
+pub const VARIABLE_RUNTIME_ACCESS: u32 = 0x4;
+
+/// `EFI_GUID`, variables are namespaced by a vendor guid.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+impl Guid {
+    pub const fn new(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Guid {
+        Guid { data1, data2, data3, data4 }
+    }
+}
+
+/// `EFI_GLOBAL_VARIABLE`, the vendor of the variables defined by the spec such as `BootOrder`.
+pub const GLOBAL_VARIABLE: Guid = Guid::new(0x8BE4DF61, 0x93CA, 0x11D2, [0xAA, 0x0D, 0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C]);
+
+/// `EFI_TIME`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pad1: u8,
+    pub nanosecond: u32,
+    /// Minutes from UTC, 0x7FF if the time is local time
+    pub time_zone: i16,
+    pub daylight: u8,
+    pad2: u8,
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+/// `EFI_TIME_CAPABILITIES`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeCapabilities {
+    resolution: u32,
+    accuracy: u32,
+    sets_to_zero: u8,
+}
+
+/// `EFI_RESET_TYPE`
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetType {
+    Cold = 0,
+    Warm = 1,
+    Shutdown = 2,
+}
+
+/// An `EFI_STATUS` error returned by a runtime service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    /// The bootloader didn't pass the runtime services table, or [`init`] hasn't been called
+    NotAvailable,
+    InvalidParameter,
+    Unsupported,
+    /// Holds the size the buffer needs to be
+    BufferTooSmall(usize),
+    DeviceError,
+    WriteProtected,
+    OutOfResources,
+    NotFound,
+    SecurityViolation,
+    Other(u64),
+}
+
+impl RuntimeError {
+    fn from_status(status: u64) -> RuntimeError {
+        match status & !ERROR_BIT {
+            2 => RuntimeError::InvalidParameter,
+            3 => RuntimeError::Unsupported,
+            5 => RuntimeError::BufferTooSmall(0),
+            7 => RuntimeError::DeviceError,
+            8 => RuntimeError::WriteProtected,
+            9 => RuntimeError::OutOfResources,
+            14 => RuntimeError::NotFound,
+            26 => RuntimeError::SecurityViolation,
+            _ => RuntimeError::Other(status),
+        }
+    }
+}
+
+// Turns an EFI_STATUS into a result, warnings don't have the error bit and count as success.
+fn check(status: u64) -> Result<(), RuntimeError> {
+    if status & ERROR_BIT == 0 {
+        return Ok(());
+    }
+    return Err(RuntimeError::from_status(status));
+}
+
+#[repr(C)]
+struct TableHeader {
+    signature: u64,
+    revision: u32,
+    header_size: u32,
+    crc32: u32,
+    reserved: u32,
+}
+
+/// `EFI_RUNTIME_SERVICES`, the firmware uses the Microsoft x64 calling convention.
+#[repr(C)]
+struct RuntimeServicesTable {
+    header: TableHeader,
+    get_time: extern "win64" fn(time: *mut Time, capabilities: *mut TimeCapabilities) -> u64,
+    set_time: u64,
+    get_wakeup_time: u64,
+    set_wakeup_time: u64,
+    set_virtual_address_map: u64,
+    convert_pointer: u64,
+    get_variable: extern "win64" fn(
+        name: *const u16, vendor: *const Guid, attributes: *mut u32, data_size: *mut usize, data: *mut u8,
+    ) -> u64,
+    get_next_variable_name: u64,
+    set_variable: extern "win64" fn(
+        name: *const u16, vendor: *const Guid, attributes: u32, data_size: usize, data: *const u8,
+    ) -> u64,
+    get_next_high_monotonic_count: u64,
+    reset_system: extern "win64" fn(reset_type: ResetType, status: u64, data_size: usize, data: *const u8) -> !,
+}
+
+// Virtual address of the runtime services table, 0 if there isn't one. The firmware isn't reentrant so every call is
+// made holding this lock.
+static RUNTIME_SERVICES: IrqSpinLock<u64> = IrqSpinLock::new(0);
+
+/// # Init
+///
+/// Checks the runtime services table the bootloader found, calls to the other functions fail with
+/// [`RuntimeError::NotAvailable`] until this succeeds. Must be called after paging is set up.
+///
+/// ## Arguments
+/// * 'address' - physical address of the table from the boot info, 0 if there is none
+pub fn init(address: u64) -> () {
+    if address == 0 {
+        println!(0x00FF2222; "No UEFI runtime services");
+        return;
+    }
+    let table = unsafe { &*(phys_to_virt(address) as *const RuntimeServicesTable) };
+    if table.header.signature != RUNTIME_SERVICES_SIGNATURE {
+        println!(0x00FF2222; "UEFI runtime services table has a bad signature");
+        return;
+    }
+    *RUNTIME_SERVICES.lock() = phys_to_virt(address);
+    println!(0x0022FF22; "-- Found UEFI runtime services, revision {}.{}",
+        table.header.revision >> 16, (table.header.revision & 0xFFFF) / 10);
+
+    match get_time() {
+        Ok(time) => println!("Firmware time: {}", time),
+        Err(e) => println!("Firmware time unavailable: {:?}", e),
+    }
+}
+
+// Runs f with the runtime services table locked.
+fn with_table<T>(f: impl FnOnce(&RuntimeServicesTable) -> Result<T, RuntimeError>) -> Result<T, RuntimeError> {
+    let address = RUNTIME_SERVICES.lock();
+    if *address == 0 {
+        return Err(RuntimeError::NotAvailable);
+    }
+    return f(unsafe { &*(*address as *const RuntimeServicesTable) });
+}
+
+// Variable names are null terminated UCS-2.
+fn encode_name(name: &str) -> Vec<u16> {
+    name.encode_utf16().chain(core::iter::once(0)).collect()
+}
+
+/// Returns the current time from the firmware's RTC driver.
+pub fn get_time() -> Result<Time, RuntimeError> {
+    with_table(|table| {
+        let mut time = Time::default();
+        check((table.get_time)(&mut time, core::ptr::null_mut()))?;
+        return Ok(time);
+    })
+}
+
+/// # Get variable
+///
+/// Reads an NVRAM variable into `buffer`.
+///
+/// ## Arguments
+/// * 'name' - name of the variable
+/// * 'vendor' - guid the variable is namespaced under
+/// * 'buffer' - where the data is written
+///
+/// ## Returns
+/// * 'Result<(usize, u32), RuntimeError>' - the size of the data and its attributes, [`RuntimeError::BufferTooSmall`]
+///   holds the size needed
+pub fn get_variable(name: &str, vendor: &Guid, buffer: &mut [u8]) -> Result<(usize, u32), RuntimeError> {
+    let name = encode_name(name);
+    with_table(|table| {
+        let mut attributes: u32 = 0;
+        let mut size = buffer.len();
+        let status = (table.get_variable)(name.as_ptr(), vendor, &mut attributes, &mut size, buffer.as_mut_ptr());
+        match check(status) {
+            Ok(()) => Ok((size, attributes)),
+            Err(RuntimeError::BufferTooSmall(_)) => Err(RuntimeError::BufferTooSmall(size)),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// # Set variable
+///
+/// Creates, replaces or deletes an NVRAM variable.
+///
+/// ## Arguments
+/// * 'name' - name of the variable
+/// * 'vendor' - guid the variable is namespaced under
+/// * 'attributes' - the `VARIABLE_*` flags, [`VARIABLE_NON_VOLATILE`] keeps it across resets
+/// * 'data' - the new contents, empty deletes the variable
+pub fn set_variable(name: &str, vendor: &Guid, attributes: u32, data: &[u8]) -> Result<(), RuntimeError> {
+    let name = encode_name(name);
+    with_table(|table| {
+        check((table.set_variable)(name.as_ptr(), vendor, attributes, data.len(), data.as_ptr()))
+    })
+}
+
+/// # Reset system
+///
+/// Resets or powers off the machine through the firmware.
+///
+/// ## Returns
+/// * 'RuntimeError' - only returns if runtime services aren't available
+pub fn reset_system(reset_type: ResetType) -> RuntimeError {
+    let address = RUNTIME_SERVICES.lock();
+    if *address == 0 {
+        return RuntimeError::NotAvailable;
+    }
+    let table = unsafe { &*(*address as *const RuntimeServicesTable) };
+    (table.reset_system)(reset_type, 0, 0, core::ptr::null());
+}
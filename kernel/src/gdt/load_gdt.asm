@@ -17,9 +17,8 @@ reload_segments:
    MOV   AX, 0x10 ; 0x10 is a stand-in for your data segment
    MOV   DS, AX
    MOV   ES, AX
-   MOV   FS, AX
-   MOV   GS, AX
    MOV   SS, AX
+   ; FS and GS are left alone, loading them would clear the GS base the per-CPU area is found through
    RET
 
 load_tss:
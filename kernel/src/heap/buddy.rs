@@ -0,0 +1,256 @@
+//! # Power of two buddy allocator
+//! 
+//! Memory is split into blocks whose sizes are powers of two and which are aligned to their own size. Allocations are
+//! rounded up to the nearest block size, so there is internal waste, but freeing merges a block with its buddy in
+//! constant time per order which keeps external fragmentation low.
+
+use super::{HeapAllocator, HeapStats, HEAP_MAX_SIZE};
+use core::alloc::Layout;
+use core::ptr::null_mut;
+
+// Smallest block is 16 bytes, enough to hold a FreeBlock
+const MIN_ORDER: usize = 4;
+// Largest block is the whole heap, so any allocation that fits in the heap has a block order
+const MAX_ORDER: usize = HEAP_MAX_SIZE.trailing_zeros() as usize;
+const ORDERS: usize = MAX_ORDER - MIN_ORDER + 1;
+
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+/// # BuddyAllocator
+/// 
+/// Keeps one free list per block order, from 16 bytes up to [`HEAP_MAX_SIZE`]. A block has to be aligned to its size
+/// inside the heap, so in practice the largest allocation that can succeed is about half the heap.
+pub struct BuddyAllocator {
+    free_lists: [*mut FreeBlock; ORDERS],
+    size: usize,
+    used: usize,
+}
+
+// The allocator is only ever used behind the heap's mutex.
+unsafe impl Send for BuddyAllocator {}
+
+impl BuddyAllocator {
+    /// Creates an allocator that owns no memory.
+    pub const fn new() -> BuddyAllocator {
+        BuddyAllocator {
+            free_lists: [null_mut(); ORDERS],
+            size: 0,
+            used: 0,
+        }
+    }
+
+    // The order of the smallest block that satisfies layout, None if it is bigger than the largest block
+    fn order_for(layout: Layout) -> Option<usize> {
+        let size = if layout.size() > layout.align() { layout.size() } else { layout.align() };
+        let order = (size.next_power_of_two().trailing_zeros() as usize).max(MIN_ORDER);
+        if order > MAX_ORDER {
+            return None;
+        }
+        return Some(order);
+    }
+
+    unsafe fn push(&mut self, order: usize, addr: usize) -> () {
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock { next: self.free_lists[order - MIN_ORDER] });
+        self.free_lists[order - MIN_ORDER] = block;
+    }
+
+    unsafe fn pop(&mut self, order: usize) -> Option<usize> {
+        let head = self.free_lists[order - MIN_ORDER];
+        if head.is_null() {
+            return None;
+        }
+        self.free_lists[order - MIN_ORDER] = (*head).next;
+        return Some(head as usize);
+    }
+
+    // Removes addr from the free list of order, returning false if it wasn't there.
+    unsafe fn remove(&mut self, order: usize, addr: usize) -> bool {
+        let mut link: *mut *mut FreeBlock = &mut self.free_lists[order - MIN_ORDER];
+        while !(*link).is_null() {
+            if *link as usize == addr {
+                *link = (**link).next;
+                return true;
+            }
+            link = &mut (**link).next;
+        }
+        return false;
+    }
+}
+
+impl HeapAllocator for BuddyAllocator {
+    unsafe fn add_region(&mut self, start: usize, size: usize) -> () {
+        // Carve the region into the largest blocks that are aligned to their own size
+        let mut addr = (start + (1 << MIN_ORDER) - 1) & !((1 << MIN_ORDER) - 1);
+        let end = start + size;
+        while addr + (1 << MIN_ORDER) <= end {
+            let mut order = (addr.trailing_zeros() as usize).min(MAX_ORDER);
+            while addr + (1 << order) > end {
+                order -= 1;
+            }
+            self.push(order, addr);
+            self.size += 1 << order;
+            addr += 1 << order;
+        }
+    }
+
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let order = match Self::order_for(layout) {
+            Some(order) => order,
+            None => return null_mut(),
+        };
+
+        // Take the smallest block that fits and split it until it is the right size
+        for current in order..=MAX_ORDER {
+            if let Some(addr) = self.pop(current) {
+                for split in (order..current).rev() {
+                    self.push(split, addr + (1 << split));
+                }
+                self.used += 1 << order;
+                return addr as *mut u8;
+            }
+        }
+        return null_mut();
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) -> () {
+        let mut order = match Self::order_for(layout) {
+            Some(order) => order,
+            None => return,
+        };
+        self.used -= 1 << order;
+
+        // Keep merging while the buddy is free as well
+        let mut addr = ptr as usize;
+        while order < MAX_ORDER {
+            let buddy = addr ^ (1 << order);
+            if !self.remove(order, buddy) {
+                break;
+            }
+            addr = if addr < buddy { addr } else { buddy };
+            order += 1;
+        }
+        self.push(order, addr);
+    }
+
+    fn region_size_for(layout: Layout, start: usize) -> usize {
+        // Up to the first address aligned to the block size, then the block itself
+        match Self::order_for(layout) {
+            Some(order) => {
+                let block = 1 << order;
+                ((start + block - 1) & !(block - 1)) + block - start
+            },
+            None => layout.size() + layout.align(),
+        }
+    }
+
+    fn stats(&self) -> HeapStats {
+        let mut free_blocks = 0;
+        let mut largest_free = 0;
+        for (i, head) in self.free_lists.iter().enumerate() {
+            let mut block = *head;
+            while !block.is_null() {
+                free_blocks += 1;
+                largest_free = 1 << (i + MIN_ORDER);
+                block = unsafe { (*block).next };
+            }
+        }
+        HeapStats {
+            size: self.size,
+            used: self.used,
+            free: self.size - self.used,
+            largest_free,
+            free_blocks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const ARENA_SIZE: usize = 0x4000;
+
+    // Aligned to its size so the whole arena is a single block
+    #[repr(C, align(16384))]
+    struct Arena([u8; ARENA_SIZE]);
+
+    static mut ARENA: Arena = Arena([0; ARENA_SIZE]);
+
+    // A fresh allocator owning the whole arena, tests run one at a time so they can share it
+    fn allocator() -> (BuddyAllocator, usize) {
+        let start = addr_of_mut!(ARENA) as usize;
+        let mut allocator = BuddyAllocator::new();
+        unsafe { allocator.add_region(start, ARENA_SIZE); }
+        return (allocator, start);
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test_case]
+    fn allocation_round_trip() {
+        let (mut allocator, start) = allocator();
+        let ptr = unsafe { allocator.allocate(layout(100, 8)) };
+        assert_eq!(ptr as usize, start);
+        let stats = allocator.stats();
+        assert_eq!(stats.used, 128);
+        // Splitting the arena down to 128 bytes leaves one free block of every size from 128 bytes to half the arena
+        assert_eq!(stats.free_blocks, 7);
+        assert_eq!(stats.largest_free, ARENA_SIZE / 2);
+
+        unsafe { allocator.deallocate(ptr, layout(100, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.used, 0);
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE);
+    }
+
+    #[test_case]
+    fn buddies_merge_when_both_are_free() {
+        let (mut allocator, start) = allocator();
+        let a = unsafe { allocator.allocate(layout(128, 8)) };
+        let b = unsafe { allocator.allocate(layout(128, 8)) };
+        assert_eq!(b as usize, start + 128);
+
+        unsafe { allocator.deallocate(a, layout(128, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.free_blocks, 7);
+        assert_eq!(stats.largest_free, ARENA_SIZE / 2);
+        assert!(stats.fragmentation() > 0);
+
+        unsafe { allocator.deallocate(b, layout(128, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE);
+        assert_eq!(stats.fragmentation(), 0);
+    }
+
+    #[test_case]
+    fn blocks_are_aligned_to_their_size() {
+        let (mut allocator, _) = allocator();
+        unsafe { allocator.allocate(layout(16, 8)); }
+        let ptr = unsafe { allocator.allocate(layout(64, 1024)) };
+        assert_eq!(ptr as usize % 1024, 0);
+        assert_eq!(allocator.stats().used, 16 + 1024);
+    }
+
+    #[test_case]
+    fn fails_when_no_block_is_big_enough() {
+        let (mut allocator, _) = allocator();
+        let all = unsafe { allocator.allocate(layout(ARENA_SIZE, 8)) };
+        assert!(!all.is_null());
+        assert!(unsafe { allocator.allocate(layout(16, 8)) }.is_null());
+        let stats = allocator.stats();
+        assert_eq!(stats.free, 0);
+        assert_eq!(stats.free_blocks, 0);
+
+        unsafe { allocator.deallocate(all, layout(ARENA_SIZE, 8)); }
+        assert!(unsafe { allocator.allocate(layout(ARENA_SIZE + 1, 8)) }.is_null());
+        assert!(!unsafe { allocator.allocate(layout(16, 8)) }.is_null());
+    }
+}
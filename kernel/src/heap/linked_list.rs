@@ -0,0 +1,265 @@
+//! # First fit linked list allocator
+//! 
+//! Free blocks are kept in a list sorted by address, each block storing its size and the next block inside itself.
+//! Freed blocks are merged with their neighbours so the heap doesn't break up into unusably small pieces.
+
+use super::{HeapAllocator, HeapStats};
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+
+// Header written at the start of every free block
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// # LinkedListAllocator
+/// 
+/// Hands out memory from the regions given to it with [`HeapAllocator::add_region`].
+pub struct LinkedListAllocator {
+    head: *mut FreeBlock,
+    size: usize,
+    used: usize,
+}
+
+// The allocator is only ever used behind the heap's mutex.
+unsafe impl Send for LinkedListAllocator {}
+
+impl LinkedListAllocator {
+    /// Creates an allocator that owns no memory.
+    pub const fn new() -> LinkedListAllocator {
+        LinkedListAllocator {
+            head: null_mut(),
+            size: 0,
+            used: 0,
+        }
+    }
+
+    /// Smallest block the allocator will hand out or keep in its free list.
+    pub const fn min_block_size() -> usize {
+        size_of::<FreeBlock>()
+    }
+
+    // Rounds the layout up so the block can hold a FreeBlock header once it is freed.
+    fn block_size(layout: Layout) -> usize {
+        let size = if layout.size() < Self::min_block_size() { Self::min_block_size() } else { layout.size() };
+        return align_up(size, align_of::<FreeBlock>());
+    }
+
+    // Inserts a block into the address sorted free list, merging it with adjacent blocks.
+    unsafe fn insert_free(&mut self, start: usize, size: usize) -> () {
+        let mut previous: *mut FreeBlock = null_mut();
+        let mut current = self.head;
+        while !current.is_null() && (current as usize) < start {
+            previous = current;
+            current = (*current).next;
+        }
+
+        let block = start as *mut FreeBlock;
+        block.write(FreeBlock { size, next: current });
+
+        // Merge with the following block
+        if !current.is_null() && start + size == current as usize {
+            (*block).size += (*current).size;
+            (*block).next = (*current).next;
+        }
+
+        // Merge with the previous block, or link it in front of this one
+        if previous.is_null() {
+            self.head = block;
+        } else if previous as usize + (*previous).size == start {
+            (*previous).size += (*block).size;
+            (*previous).next = (*block).next;
+        } else {
+            (*previous).next = block;
+        }
+    }
+}
+
+impl HeapAllocator for LinkedListAllocator {
+    unsafe fn add_region(&mut self, start: usize, size: usize) -> () {
+        let aligned_start = align_up(start, align_of::<FreeBlock>());
+        if aligned_start + Self::min_block_size() > start + size {
+            return;
+        }
+        let size = size - (aligned_start - start);
+        self.size += size;
+        self.insert_free(aligned_start, size);
+    }
+
+    // Finds the first free block that fits layout, splitting off whatever is left over
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let size = Self::block_size(layout);
+        let mut previous: *mut FreeBlock = null_mut();
+        let mut current = self.head;
+
+        while !current.is_null() {
+            let block_start = current as usize;
+            let block_end = block_start + (*current).size;
+            let alloc_start = align_up(block_start, layout.align());
+            let alloc_end = alloc_start + size;
+
+            // Padding around the allocation has to be big enough to stay a free block itself
+            let front_padding = alloc_start - block_start;
+            let back_padding = block_end.saturating_sub(alloc_end);
+            let fits_front = front_padding == 0 || front_padding >= Self::min_block_size();
+            let fits_back = back_padding == 0 || back_padding >= Self::min_block_size();
+            if alloc_end <= block_end && fits_front && fits_back {
+                let next = (*current).next;
+
+                // Unlink the block, then give back the unused front and back
+                if previous.is_null() {
+                    self.head = next;
+                } else {
+                    (*previous).next = next;
+                }
+
+                if back_padding > 0 {
+                    self.insert_free(alloc_end, back_padding);
+                }
+                if front_padding > 0 {
+                    self.insert_free(block_start, front_padding);
+                }
+
+                self.used += size;
+                return alloc_start as *mut u8;
+            }
+
+            previous = current;
+            current = (*current).next;
+        }
+        return null_mut();
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) -> () {
+        let size = Self::block_size(layout);
+        self.used -= size;
+        self.insert_free(ptr as usize, size);
+    }
+
+    fn stats(&self) -> HeapStats {
+        let mut free_blocks = 0;
+        let mut largest_free = 0;
+        let mut block = self.head;
+        while !block.is_null() {
+            unsafe {
+                free_blocks += 1;
+                if (*block).size > largest_free {
+                    largest_free = (*block).size;
+                }
+                block = (*block).next;
+            }
+        }
+        HeapStats {
+            size: self.size,
+            used: self.used,
+            free: self.size - self.used,
+            largest_free,
+            free_blocks,
+        }
+    }
+}
+
+// Rounds addr up to the next multiple of align, align must be a power of two.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const ARENA_SIZE: usize = 0x4000;
+
+    #[repr(C, align(4096))]
+    struct Arena([u8; ARENA_SIZE]);
+
+    static mut ARENA: Arena = Arena([0; ARENA_SIZE]);
+
+    // A fresh allocator owning the whole arena, tests run one at a time so they can share it
+    fn allocator() -> (LinkedListAllocator, usize) {
+        let start = addr_of_mut!(ARENA) as usize;
+        let mut allocator = LinkedListAllocator::new();
+        unsafe { allocator.add_region(start, ARENA_SIZE); }
+        return (allocator, start);
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test_case]
+    fn allocation_round_trip() {
+        let (mut allocator, start) = allocator();
+        let ptr = unsafe { allocator.allocate(layout(64, 8)) };
+        assert_eq!(ptr as usize, start);
+        let stats = allocator.stats();
+        assert_eq!(stats.size, ARENA_SIZE);
+        assert_eq!(stats.used, 64);
+        assert_eq!(stats.free, ARENA_SIZE - 64);
+
+        unsafe { allocator.deallocate(ptr, layout(64, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.used, 0);
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE);
+    }
+
+    #[test_case]
+    fn freed_blocks_merge_with_their_neighbours() {
+        let (mut allocator, _) = allocator();
+        let a = unsafe { allocator.allocate(layout(64, 8)) };
+        let b = unsafe { allocator.allocate(layout(64, 8)) };
+        let c = unsafe { allocator.allocate(layout(64, 8)) };
+
+        // c joins the free memory after it, a is on its own
+        unsafe { allocator.deallocate(a, layout(64, 8)); }
+        unsafe { allocator.deallocate(c, layout(64, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.free_blocks, 2);
+        assert_eq!(stats.largest_free, ARENA_SIZE - 128);
+        assert!(stats.fragmentation() > 0);
+
+        // b joins both
+        unsafe { allocator.deallocate(b, layout(64, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE);
+        assert_eq!(stats.fragmentation(), 0);
+    }
+
+    #[test_case]
+    fn padding_before_an_aligned_block_stays_free() {
+        let (mut allocator, start) = allocator();
+        let small = unsafe { allocator.allocate(layout(16, 8)) };
+        let aligned = unsafe { allocator.allocate(layout(32, 256)) };
+        assert_eq!(aligned as usize, start + 256);
+        assert_eq!(allocator.stats().free_blocks, 2);
+
+        // The padding is used by the next allocation that fits in it
+        assert_eq!(unsafe { allocator.allocate(layout(16, 8)) } as usize, start + 16);
+
+        unsafe { allocator.deallocate(small, layout(16, 8)); }
+        unsafe { allocator.deallocate(aligned, layout(32, 256)); }
+        unsafe { allocator.deallocate((start + 16) as *mut u8, layout(16, 8)); }
+        assert_eq!(allocator.stats().free_blocks, 1);
+    }
+
+    #[test_case]
+    fn fails_when_no_block_is_big_enough() {
+        let (mut allocator, _) = allocator();
+        let all = unsafe { allocator.allocate(layout(ARENA_SIZE, 8)) };
+        assert!(!all.is_null());
+        assert!(unsafe { allocator.allocate(layout(16, 8)) }.is_null());
+        let stats = allocator.stats();
+        assert_eq!(stats.free, 0);
+        assert_eq!(stats.free_blocks, 0);
+        assert_eq!(stats.fragmentation(), 0);
+
+        unsafe { allocator.deallocate(all, layout(ARENA_SIZE, 8)); }
+        assert!(unsafe { allocator.allocate(layout(ARENA_SIZE + 8, 8)) }.is_null());
+        assert!(!unsafe { allocator.allocate(layout(16, 8)) }.is_null());
+    }
+}
@@ -0,0 +1,204 @@
+//! # Kernel heap
+//! 
+//! Backs `alloc::{Box, Vec, String}` with memory at [`HEAP_START`]. The heap starts at [`HEAP_INITIAL_SIZE`] and grows
+//! a chunk at a time until it reaches [`HEAP_MAX_SIZE`]. The whole range is a demand paged region, so frames are only
+//! taken from the frame allocator when the page fault handler sees a page touched for the first time.
+//! 
+//! The allocation strategy is picked with a cargo feature:
+//! - default: [`linked_list::LinkedListAllocator`], first fit with coalescing, the least memory overhead
+//! - `heap_buddy`: [`buddy::BuddyAllocator`], power of two blocks, fast frees and little external fragmentation
+//! - `heap_slab`: [`slab::SlabAllocator`], object caches for small fixed size allocations over a linked list
+//! 
+//! [`KernelHeap`]
+//! 
+//! [`HeapAllocator`]
+//! 
+//! [`init_heap`]
+
+pub mod buddy;
+pub mod linked_list;
+pub mod slab;
+
+use crate::math::RoundMath;
+use crate::paging::regions::{register_region, RegionKind, VirtualRegion};
+use crate::paging::{FRAME_SIZE, PageFlags};
+use crate::println;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::null_mut;
+use crate::sync::IrqSpinLock;
+
+#[cfg(all(feature = "heap_buddy", feature = "heap_slab"))]
+compile_error!("Only one of the heap_buddy and heap_slab features can be enabled");
+
+#[cfg(feature = "heap_buddy")]
+type Allocator = buddy::BuddyAllocator;
+#[cfg(all(feature = "heap_slab", not(feature = "heap_buddy")))]
+type Allocator = slab::SlabAllocator;
+#[cfg(not(any(feature = "heap_buddy", feature = "heap_slab")))]
+type Allocator = linked_list::LinkedListAllocator;
+
+/// Virtual address the heap starts at.
+pub const HEAP_START: u64 = 0xFFFF_C000_0000_0000;
+/// Bytes mapped by [`init_heap`].
+pub const HEAP_INITIAL_SIZE: u64 = 0x100000;
+/// The heap never grows past this many bytes.
+pub const HEAP_MAX_SIZE: u64 = 0x4000000;
+// Smallest amount the heap grows by at once, so the allocator isn't handed lots of tiny regions
+const HEAP_GROWTH: u64 = 0x100000;
+
+/// Usage statistics reported by every [`HeapAllocator`], all sizes are in bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    /// Memory owned by the allocator.
+    pub size: usize,
+    /// Memory handed out, including any rounding the strategy does.
+    pub used: usize,
+    /// Memory that can still be handed out.
+    pub free: usize,
+    /// The biggest single allocation that would currently succeed without growing.
+    pub largest_free: usize,
+    /// How many separate free blocks the free memory is split into.
+    pub free_blocks: usize,
+}
+
+impl HeapStats {
+    /// External fragmentation as a percentage, 0 when all free memory is one block.
+    pub fn fragmentation(&self) -> usize {
+        if self.free == 0 {
+            return 0;
+        }
+        return 100 - (self.largest_free * 100) / self.free;
+    }
+}
+
+/// # HeapAllocator
+/// 
+/// The interface every heap strategy implements. The heap calls these with its lock held, so implementations don't
+/// need any synchronisation of their own.
+pub trait HeapAllocator: Send {
+    /// # Add region
+    /// 
+    /// Gives the memory from `start` to `start + size` to the allocator. Regions are added in increasing address order
+    /// and each one directly follows the last.
+    /// 
+    /// ## Safety
+    /// The memory must be mapped, writable, unused and never handed to the allocator twice.
+    unsafe fn add_region(&mut self, start: usize, size: usize) -> ();
+
+    /// # Allocate
+    /// 
+    /// ## Returns
+    /// * '*mut u8' - Memory fitting `layout`, null if the allocator has nothing big enough
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8;
+
+    /// Returns memory from [`HeapAllocator::allocate`], `layout` must be the one it was allocated with.
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) -> ();
+
+    /// How big a new region starting at `start` must be to guarantee an allocation of `layout` succeeds in it.
+    fn region_size_for(layout: Layout, _start: usize) -> usize where Self: Sized {
+        layout.size() + layout.align()
+    }
+
+    fn stats(&self) -> HeapStats;
+}
+
+struct HeapState {
+    allocator: Allocator,
+    end: u64,
+}
+
+/// # KernelHeap
+/// 
+/// The kernel's `#[global_allocator]`, wraps whichever [`HeapAllocator`] was selected and grows the heap when it
+/// runs out.
+pub struct KernelHeap {
+    state: IrqSpinLock<HeapState>,
+}
+
+#[global_allocator]
+static HEAP: KernelHeap = KernelHeap::new();
+
+impl KernelHeap {
+    const fn new() -> KernelHeap {
+        KernelHeap {
+            state: IrqSpinLock::new(HeapState {
+                allocator: Allocator::new(),
+                end: HEAP_START,
+            }),
+        }
+    }
+}
+
+impl HeapState {
+    // Hands at least size more bytes on the end of the heap to the allocator, the pages are mapped on first access.
+    fn grow(&mut self, size: u64) -> bool {
+        let size = size.ceil(FRAME_SIZE);
+        if self.end + size > HEAP_START + HEAP_MAX_SIZE {
+            return false;
+        }
+
+        unsafe {
+            self.allocator.add_region(self.end as usize, size as usize);
+        }
+        self.end += size;
+        return true;
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut state = self.state.lock();
+        let ptr = state.allocator.allocate(layout);
+        if !ptr.is_null() {
+            return ptr;
+        }
+
+        let needed = Allocator::region_size_for(layout, state.end as usize) as u64;
+        let growth = if needed > HEAP_GROWTH { needed } else { HEAP_GROWTH };
+        if !state.grow(growth) {
+            return null_mut();
+        }
+        return state.allocator.allocate(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.state.lock().allocator.deallocate(ptr, layout);
+    }
+}
+
+/// Sets up the initial heap, `alloc` types can be used once this returns.
+pub fn init_heap() -> () {
+    let registered = register_region(VirtualRegion {
+        start: HEAP_START,
+        end: HEAP_START + HEAP_MAX_SIZE,
+        kind: RegionKind::Demand(PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE),
+        name: "kernel heap",
+    });
+    if !registered {
+        panic!("Failed to register the kernel heap region");
+    }
+
+    let mut state = HEAP.state.lock();
+    if !state.grow(HEAP_INITIAL_SIZE) {
+        panic!("Failed to create the kernel heap");
+    }
+    println!(0x0022FF22; "-- Initialised kernel heap at {:#x}, {} KiB", HEAP_START, state.allocator.stats().size / 1024);
+}
+
+/// Current usage of the kernel heap.
+pub fn stats() -> HeapStats {
+    HEAP.state.lock().allocator.stats()
+}
+
+/// Prints the heap's usage and fragmentation.
+pub fn print_stats() -> () {
+    let stats = stats();
+    println!("Heap size: {} KiB, used: {} B, free: {} B", stats.size / 1024, stats.used, stats.free);
+    println!("Heap free blocks: {}, largest: {} B, fragmentation: {}%",
+        stats.free_blocks, stats.largest_free, stats.fragmentation());
+}
+
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    panic!("Kernel heap allocation failed: {:?}", layout);
+}
@@ -0,0 +1,285 @@
+//! # Slab allocator
+//! 
+//! Small allocations are served from caches of equally sized objects, each cache carving whole slabs out of a
+//! [`LinkedListAllocator`]. Kernel objects such as `Arc<Thread>`s, thread names and the boxed closures threads start
+//! with are allocated and freed constantly at a handful of fixed sizes, so a cache hit is a single list pop and freed
+//! objects never fragment the backing heap.
+//! Anything larger than the biggest cache goes straight to the backing allocator.
+
+use super::linked_list::LinkedListAllocator;
+use super::{HeapAllocator, HeapStats};
+use core::alloc::Layout;
+use core::ptr::null_mut;
+
+const OBJECT_SIZES: [usize; 9] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+const SLAB_SIZE: usize = 0x1000;
+
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+/// # SlabCache
+/// 
+/// A free list of objects of one size, refilled a slab at a time.
+pub struct SlabCache {
+    object_size: usize,
+    free: *mut FreeObject,
+    slabs: usize,
+    free_objects: usize,
+}
+
+impl SlabCache {
+    /// Creates an empty cache for objects of `object_size` bytes, which must be a power of two.
+    pub const fn new(object_size: usize) -> SlabCache {
+        SlabCache {
+            object_size,
+            free: null_mut(),
+            slabs: 0,
+            free_objects: 0,
+        }
+    }
+
+    /// Size of the objects in this cache.
+    pub fn object_size(&self) -> usize {
+        self.object_size
+    }
+
+    /// Number of slabs carved up by this cache.
+    pub fn slabs(&self) -> usize {
+        self.slabs
+    }
+
+    /// Objects that are ready to be handed out without refilling.
+    pub fn free_objects(&self) -> usize {
+        self.free_objects
+    }
+
+    // Splits a fresh slab into objects and pushes them onto the free list.
+    unsafe fn refill(&mut self, slab: *mut u8) -> () {
+        for i in 0..SLAB_SIZE / self.object_size {
+            self.push(slab.add(i * self.object_size));
+        }
+        self.slabs += 1;
+    }
+
+    unsafe fn push(&mut self, object: *mut u8) -> () {
+        let object = object as *mut FreeObject;
+        object.write(FreeObject { next: self.free });
+        self.free = object;
+        self.free_objects += 1;
+    }
+
+    unsafe fn pop(&mut self) -> *mut u8 {
+        let object = self.free;
+        if !object.is_null() {
+            self.free = (*object).next;
+            self.free_objects -= 1;
+        }
+        return object as *mut u8;
+    }
+}
+
+/// # SlabAllocator
+/// 
+/// One [`SlabCache`] per power of two from 8 bytes to 2 KiB, backed by a [`LinkedListAllocator`].
+pub struct SlabAllocator {
+    caches: [SlabCache; OBJECT_SIZES.len()],
+    backing: LinkedListAllocator,
+}
+
+// The allocator is only ever used behind the heap's mutex.
+unsafe impl Send for SlabAllocator {}
+
+impl SlabAllocator {
+    /// Creates an allocator that owns no memory.
+    pub const fn new() -> SlabAllocator {
+        SlabAllocator {
+            caches: [
+                SlabCache::new(OBJECT_SIZES[0]),
+                SlabCache::new(OBJECT_SIZES[1]),
+                SlabCache::new(OBJECT_SIZES[2]),
+                SlabCache::new(OBJECT_SIZES[3]),
+                SlabCache::new(OBJECT_SIZES[4]),
+                SlabCache::new(OBJECT_SIZES[5]),
+                SlabCache::new(OBJECT_SIZES[6]),
+                SlabCache::new(OBJECT_SIZES[7]),
+                SlabCache::new(OBJECT_SIZES[8]),
+            ],
+            backing: LinkedListAllocator::new(),
+        }
+    }
+
+    /// The caches, smallest object size first.
+    pub fn caches(&self) -> &[SlabCache] {
+        &self.caches
+    }
+
+    // Index of the smallest cache that fits layout, objects are aligned to their size since slabs are page aligned.
+    fn cache_index(layout: Layout) -> Option<usize> {
+        let size = if layout.size() > layout.align() { layout.size() } else { layout.align() };
+        return OBJECT_SIZES.iter().position(|&object_size| object_size >= size);
+    }
+
+    fn slab_layout() -> Layout {
+        Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap()
+    }
+}
+
+impl HeapAllocator for SlabAllocator {
+    unsafe fn add_region(&mut self, start: usize, size: usize) -> () {
+        self.backing.add_region(start, size);
+    }
+
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let index = match Self::cache_index(layout) {
+            Some(index) => index,
+            None => return self.backing.allocate(layout),
+        };
+
+        let cache = &mut self.caches[index];
+        if cache.free.is_null() {
+            let slab = self.backing.allocate(Self::slab_layout());
+            if slab.is_null() {
+                return null_mut();
+            }
+            cache.refill(slab);
+        }
+        return cache.pop();
+    }
+
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) -> () {
+        match Self::cache_index(layout) {
+            Some(index) => self.caches[index].push(ptr),
+            None => self.backing.deallocate(ptr, layout),
+        }
+    }
+
+    fn region_size_for(layout: Layout, _start: usize) -> usize {
+        // Either the allocation itself or a whole slab for one of the caches
+        let size = layout.size() + layout.align();
+        return if size > 2 * SLAB_SIZE { size } else { 2 * SLAB_SIZE };
+    }
+
+    fn stats(&self) -> HeapStats {
+        // Objects sitting in a cache count as free, but only the backing allocator can satisfy large requests
+        let backing = self.backing.stats();
+        let cached: usize = self.caches.iter().map(|cache| cache.free_objects * cache.object_size).sum();
+        let cached_blocks: usize = self.caches.iter().map(|cache| cache.free_objects).sum();
+        HeapStats {
+            size: backing.size,
+            used: backing.used - cached,
+            free: backing.free + cached,
+            largest_free: backing.largest_free,
+            free_blocks: backing.free_blocks + cached_blocks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::addr_of_mut;
+
+    const ARENA_SIZE: usize = 4 * SLAB_SIZE;
+
+    #[repr(C, align(4096))]
+    struct Arena([u8; ARENA_SIZE]);
+
+    static mut ARENA: Arena = Arena([0; ARENA_SIZE]);
+
+    // A fresh allocator owning the whole arena, tests run one at a time so they can share it
+    fn allocator() -> SlabAllocator {
+        let mut allocator = SlabAllocator::new();
+        unsafe { allocator.add_region(addr_of_mut!(ARENA) as usize, ARENA_SIZE); }
+        return allocator;
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test_case]
+    fn small_allocation_round_trip() {
+        let mut allocator = allocator();
+        let ptr = unsafe { allocator.allocate(layout(24, 8)) };
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 32, 0);
+        assert_eq!(allocator.caches()[2].slabs(), 1);
+        assert_eq!(allocator.caches()[2].free_objects(), SLAB_SIZE / 32 - 1);
+
+        unsafe { allocator.deallocate(ptr, layout(24, 8)); }
+        assert_eq!(allocator.caches()[2].free_objects(), SLAB_SIZE / 32);
+        // The object just freed is the first one handed out again
+        assert_eq!(unsafe { allocator.allocate(layout(32, 8)) }, ptr);
+    }
+
+    #[test_case]
+    fn freed_objects_are_reused() {
+        let mut allocator = allocator();
+        let per_slab = SLAB_SIZE / 64;
+        let mut objects = [null_mut(); SLAB_SIZE / 64 + 1];
+        for object in objects.iter_mut() {
+            *object = unsafe { allocator.allocate(layout(64, 8)) };
+            assert!(!object.is_null());
+        }
+        assert_eq!(allocator.caches()[3].slabs(), 2);
+
+        for object in objects.iter() {
+            unsafe { allocator.deallocate(*object, layout(64, 8)); }
+        }
+        assert_eq!(allocator.caches()[3].free_objects(), 2 * per_slab);
+        for _ in 0..2 * per_slab {
+            assert!(!unsafe { allocator.allocate(layout(64, 8)) }.is_null());
+        }
+        assert_eq!(allocator.caches()[3].slabs(), 2);
+    }
+
+    #[test_case]
+    fn objects_are_aligned_to_their_cache() {
+        let mut allocator = allocator();
+        let ptr = unsafe { allocator.allocate(layout(8, 512)) };
+        assert_eq!(ptr as usize % 512, 0);
+        assert_eq!(allocator.caches()[6].slabs(), 1);
+    }
+
+    #[test_case]
+    fn large_allocations_use_the_backing_allocator() {
+        let mut allocator = allocator();
+        let ptr = unsafe { allocator.allocate(layout(2 * SLAB_SIZE, 8)) };
+        assert!(!ptr.is_null());
+        assert!(allocator.caches().iter().all(|cache| cache.slabs() == 0));
+        assert_eq!(allocator.stats().used, 2 * SLAB_SIZE);
+
+        unsafe { allocator.deallocate(ptr, layout(2 * SLAB_SIZE, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.used, 0);
+        assert_eq!(stats.free_blocks, 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE);
+    }
+
+    #[test_case]
+    fn fails_when_no_slab_can_be_carved() {
+        let mut allocator = allocator();
+        let all = unsafe { allocator.allocate(layout(ARENA_SIZE, 8)) };
+        assert!(!all.is_null());
+        assert!(unsafe { allocator.allocate(layout(8, 8)) }.is_null());
+        assert!(unsafe { allocator.allocate(layout(ARENA_SIZE, 8)) }.is_null());
+
+        unsafe { allocator.deallocate(all, layout(ARENA_SIZE, 8)); }
+        assert!(!unsafe { allocator.allocate(layout(8, 8)) }.is_null());
+    }
+
+    #[test_case]
+    fn cached_objects_count_as_free() {
+        let mut allocator = allocator();
+        unsafe { allocator.allocate(layout(32, 8)); }
+        let stats = allocator.stats();
+        assert_eq!(stats.size, ARENA_SIZE);
+        assert_eq!(stats.used, 32);
+        assert_eq!(stats.free, ARENA_SIZE - 32);
+        // The rest of the slab's objects plus what is left of the backing region
+        assert_eq!(stats.free_blocks, SLAB_SIZE / 32 - 1 + 1);
+        assert_eq!(stats.largest_free, ARENA_SIZE - SLAB_SIZE);
+        assert_eq!(stats.fragmentation(), 25);
+    }
+}
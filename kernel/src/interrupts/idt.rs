@@ -3,7 +3,7 @@
 //! 
 //! [`Gate`]
 //! 
-//! [`GateOptions`]
+//! [`GateOptions`] re-exported from [`kernel_lib::idt`]
 //! 
 //! [`IDT`]
 //! 
@@ -12,6 +12,7 @@
 //! 
 
 use core::fmt;
+pub use kernel_lib::idt::GateOptions;
 use core::{marker::PhantomData};
 
 /// # Gate
@@ -116,61 +117,6 @@ impl<F> Gate<F> {
     }
 }
 
-/// # GateOptions
-/// 
-/// A struct for the easy creation and manipulation of the options bitfields in an IDT gate
-#[derive(Debug, Clone, Copy)]
-#[repr(C, packed)]
-pub struct GateOptions{
-    ist: u8,
-    options:u8,
-}
-
-
-impl GateOptions{
-
-    /// ## Creates a new GateOptions bitfield for an interrupt gate
-    pub fn new_interrupt_options() -> GateOptions {
-        return GateOptions{ ist: 0, options: 0b00001110}
-    }
-
-    /// ## Creates a new GateOptions bitfield for an call gate
-    pub fn new_call_options() -> GateOptions {
-        return GateOptions{ ist: 0, options: 0b00001100}
-    }
-
-    /// ## Creates a new GateOptions bitfield for an trap gate
-    pub fn new_trap_options() -> GateOptions {
-        return GateOptions{ ist: 0, options: 0b00001111}
-    }
-
-    /// # Set present bit
-    /// 
-    /// Sets the present bit in the options bitfield, telling the CPU that the address in the gate points to a function.
-    /// 
-    /// # Returns
-    /// * 'Self' - Itself for method chaining
-    pub fn set_present(&mut self) -> &mut Self {
-        self.options |= 1 << 7u8;
-        self
-    }
-
-    /// # Clear present bit
-    /// 
-    /// Clears the present bit in the options bitfield, telling the CPU that the address in the gate points to a function.
-    /// 
-    /// # Returns
-    /// * 'Self' - Itself for method chaining
-    pub fn clear_present(&mut self) -> &mut Self {
-        self.options &= !(1 << 7u8);
-        self
-    }
-
-    pub unsafe fn set_stack_index(&mut self, index: u8) -> &mut Self{
-        self.ist |= (index + 1) & 0b00000111;
-        self
-    }
-}
 
 /// # ExceptionStackFrame
 /// 
@@ -315,4 +261,22 @@ impl IDT{
 
 extern "C" {
     fn load_idt(_idt_descriptor_pointer: *const IDTDescriptor) -> ();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn gate_splits_handler_address() {
+        let mut gate = Gate::<Fault>::empty();
+        gate.init(0xFFFF_FFFF_8123_4567, GateOptions::new_interrupt_options());
+        assert_eq!(gate.offset_low, 0x4567);
+        assert_eq!(gate.offset_mid, 0x8123);
+        assert_eq!(gate.offset_high, 0xFFFF_FFFF);
+        assert_eq!(gate.segment_selector, 0x8);
+        assert_eq!(gate.get_handler_address(), 0xFFFF_FFFF_8123_4567);
+        assert_eq!(gate.options.type_attributes(), 0x8E);
+        assert_eq!(core::mem::size_of::<Gate<Fault>>(), 16);
+    }
+}
@@ -1,10 +1,13 @@
 //! # Module containing all interrupts and functions to initialise the IDT
 
 mod idt;
+mod page_fault;
 
 use lazy_static::lazy_static;
-use crate::io::{keyboard, PIC, PS2};
-use crate::{println};
+use crate::io::{self, apic, keyboard, pit, rtc, PS2};
+use crate::{asm, cpu_local, println, task};
+use crate::paging::stack;
+use core::cell::Cell;
 use idt::{IDT, GateOptions, ExceptionStackFrame};
 
 lazy_static!{
@@ -19,12 +22,26 @@ lazy_static!{
         }
         idt.general_protecion_fault.init(general_protection_handler as u64, GateOptions::new_trap_options());
 
-        // Add keyboard interrupt to the free interupt descriptor
+        // Add timer, keyboard and RTC interrupts to the free interupt descriptors
+        idt.interrupts[0].init(timer_interrupt_handler as u64, GateOptions::new_interrupt_options());
         idt.interrupts[1].init(keyboard_interrupts_handler as u64, GateOptions::new_interrupt_options());
+        idt.interrupts[8].init(rtc_interrupt_handler as u64, GateOptions::new_interrupt_options());
+        // Spurious interrupts from the APIC, and from the PIC on IRQ 7 and 15 once it is masked
+        idt.interrupts[7].init(spurious_interrupt_handler as u64, GateOptions::new_interrupt_options());
+        idt.interrupts[15].init(spurious_interrupt_handler as u64, GateOptions::new_interrupt_options());
+        idt.interrupts[apic::local::SPURIOUS_INTERRUPT_VECTOR as usize - 32]
+            .init(spurious_interrupt_handler as u64, GateOptions::new_interrupt_options());
         idt
     };
 }
 
+const RFLAGS_INTERRUPT: u64 = 1 << 9;
+
+cpu_local! {
+    // IRQ handlers running on this CPU, exceptions can interrupt a handler so this can go above 1
+    static INTERRUPT_DEPTH: Cell<u32> = Cell::new(0);
+}
+
 extern "C" {
     fn clear_interrupts() -> ();
     fn set_interrupts() -> ();
@@ -39,6 +56,43 @@ pub fn init_idt(){
     }
 }
 
+/// Loads the shared IDT on an application processor, [`init_idt`] must already have run on the BSP.
+pub fn load_idt() -> () {
+    IDTABLE.load();
+}
+
+/// Returns true if interrupts are enabled on the current CPU.
+pub fn interrupts_enabled() -> bool {
+    asm::read_rflags() & RFLAGS_INTERRUPT != 0
+}
+
+/// Runs f with interrupts off on the current CPU, they are turned back on afterwards only if they were on before.
+pub fn without_interrupts<T>(f: impl FnOnce() -> T) -> T {
+    let enabled = interrupts_enabled();
+    asm::cli();
+    let result = f();
+    if enabled {
+        asm::sti();
+    }
+    return result;
+}
+
+/// Returns true if the current CPU is running an IRQ handler.
+pub fn in_interrupt() -> bool {
+    INTERRUPT_DEPTH.get().get() > 0
+}
+
+// Bracket the body of every IRQ handler.
+fn enter_interrupt() -> () {
+    let depth = INTERRUPT_DEPTH.get();
+    depth.set(depth.get() + 1);
+}
+
+fn exit_interrupt() -> () {
+    let depth = INTERRUPT_DEPTH.get();
+    depth.set(depth.get() - 1);
+}
+
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: ExceptionStackFrame) -> (){
     println!(0x00FFFF22;  "\nEXCEPTION: BREAKPOINT");
     println!("{:#?}",stack_frame);
@@ -46,14 +100,19 @@ extern "x86-interrupt" fn breakpoint_handler(stack_frame: ExceptionStackFrame) -
 }
 
 extern "x86-interrupt" fn page_fault_handler(stack_frame: ExceptionStackFrame, page_fault_error_code: u64) -> (){
-    println!(0x00FFFF22;  "\nEXCEPTION: PAGE FAULT");
-    println!("{:#?}",stack_frame);
-    println!("Error code: {:#x}", page_fault_error_code);
-    loop{}
+    page_fault::handle_page_fault(&stack_frame, page_fault_error_code);
 }
 
 extern "x86-interrupt" fn double_fault_handler(stack_frame: ExceptionStackFrame, error_code: u64) -> !{
     println!(0x00FFFF22;  "\nEXCEPTION: DOUBLE FAULT");
+
+    // Overflowing a stack faults on its guard page, and the page fault can't be delivered on the same stack
+    let overflowed = stack::guard_page_owner(asm::read_cr2())
+        .or_else(|| stack::guard_page_owner(stack_frame.stack_pointer));
+    if let Some(name) = overflowed {
+        println!("Stack overflow on stack {}", name);
+    }
+
     println!("{:#?}",stack_frame);
     println!("Error code: {:#x}", error_code);
     loop{}
@@ -66,10 +125,35 @@ extern "x86-interrupt" fn general_protection_handler(stack_frame: ExceptionStack
     loop{}
 }
 
+extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: ExceptionStackFrame) -> () {
+    enter_interrupt();
+    pit::tick();
+    io::end_of_interrupt(0);
+    exit_interrupt();
+    // Might switch threads, this handler then finishes when the interrupted thread is switched back to
+    task::preempt();
+}
+
 extern "x86-interrupt" fn keyboard_interrupts_handler(_stack_frame: ExceptionStackFrame) -> () {
-    let scancode = PS2.lock().read_data();
-    let key_stroke = PS2.lock().keystroke_from_ps2_scancode(scancode);
+    enter_interrupt();
+    let key_stroke = {
+        let ps2 = PS2.lock();
+        let scancode = ps2.read_data();
+        ps2.keystroke_from_ps2_scancode(scancode)
+    };
 
     keyboard::handle_keyboard_for_typing(key_stroke);
-    PIC.lock().end_master();
-}
\ No newline at end of file
+    io::end_of_interrupt(1);
+    exit_interrupt();
+}
+
+extern "x86-interrupt" fn rtc_interrupt_handler(_stack_frame: ExceptionStackFrame) -> () {
+    enter_interrupt();
+    rtc::handle_interrupt();
+    io::end_of_interrupt(8);
+    exit_interrupt();
+}
+
+// Neither the local APIC nor a masked PIC expect an end of interrupt for spurious interrupts
+extern "x86-interrupt" fn spurious_interrupt_handler(_stack_frame: ExceptionStackFrame) -> () {
+}
@@ -0,0 +1,228 @@
+//! # Page fault handling
+//! 
+//! Decodes the page fault error code, resolves faults in demand paged regions and reports everything else with the
+//! page table walk for the faulting address.
+//! 
+//! [`PageFaultErrorCode`]
+//! 
+//! [`handle_page_fault`]
+
+use super::idt::ExceptionStackFrame;
+use crate::asm;
+use crate::paging::regions::{self, RegionKind};
+use crate::paging::stack;
+use crate::paging::{phys_to_virt, MapError, FRAME_ALLOCATOR, FRAME_SIZE, PAGE_TABLE_MANAGER, PageFlags};
+use crate::println;
+use crate::symbols;
+use crate::sync::{IrqSpinLock, IrqSpinLockGuard};
+use core::fmt;
+use kernel_lib::symbols::Demangle;
+
+/// # PageFaultErrorCode
+/// 
+/// The error code the CPU pushes for a page fault.\
+/// Details at https://wiki.osdev.org/Exceptions#Page_Fault
+#[derive(Clone, Copy)]
+pub struct PageFaultErrorCode(u64);
+
+impl PageFaultErrorCode {
+    pub fn new(error_code: u64) -> PageFaultErrorCode {
+        PageFaultErrorCode(error_code)
+    }
+
+    /// The page was present, so this is a protection violation rather than a missing page.
+    pub fn present(&self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    /// The access was a write, otherwise a read.
+    pub fn write(&self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    /// The access came from ring 3.
+    pub fn user(&self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// A reserved bit was set in one of the page table entries.
+    pub fn reserved(&self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// The access was an instruction fetch, only reported when NX is enabled.
+    pub fn instruction_fetch(&self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    /// The access was blocked by a protection key.
+    pub fn protection_key(&self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// The access was a shadow stack access.
+    pub fn shadow_stack(&self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+}
+
+impl fmt::Debug for PageFaultErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Page fault error")
+            .field("present", &self.present())
+            .field("write", &self.write())
+            .field("user", &self.user())
+            .field("reserved", &self.reserved())
+            .field("instruction fetch", &self.instruction_fetch())
+            .field("protection key", &self.protection_key())
+            .field("shadow stack", &self.shadow_stack())
+            .finish()
+    }
+}
+
+extern "C" {
+    static __text_start: u8;
+    static __text_end: u8;
+}
+
+/// # Handle page fault
+/// 
+/// Called by the page fault interrupt handler. Returns when the fault was resolved and the faulting instruction can
+/// be retried, otherwise reports the fault and halts.
+pub fn handle_page_fault(stack_frame: &ExceptionStackFrame, error_code: u64) -> () {
+    let address = asm::read_cr2();
+    let error = PageFaultErrorCode::new(error_code);
+
+    if let Some(name) = stack::guard_page_owner(address) {
+        println!(0x00FFFF22; "\nEXCEPTION: PAGE FAULT");
+        println!("Stack overflow on stack {}, guard page hit at {:#x}", name, address);
+        report(stack_frame, error, address);
+        loop {
+            asm::hlt();
+        }
+    }
+
+    let region = regions::find_region(address);
+    if let Some(region) = region {
+        match region.kind {
+            RegionKind::Demand(flags) if !error.present() => {
+                let error = match map_demand_page(address, flags) {
+                    Ok(()) => return,
+                    Err(error) => error,
+                };
+                println!(0x00FFFF22; "\nEXCEPTION: PAGE FAULT");
+                match error {
+                    DemandPageError::OutOfMemory => {
+                        println!("Out of memory backing {:#x} in {}", address, region.name);
+                    },
+                    DemandPageError::Locked => {
+                        println!("Fault at {:#x} in {} while the frame allocator or page tables were locked",
+                            address, region.name);
+                    },
+                    DemandPageError::Map(error) => {
+                        println!("Couldn't map {:#x} in {}: {:?}", address, region.name, error);
+                    },
+                }
+            },
+            RegionKind::Guard => {
+                println!(0x00FFFF22; "\nEXCEPTION: PAGE FAULT");
+                println!("Stack overflow: guard page of {} hit at {:#x}", region.name, address);
+            },
+            _ => {
+                println!(0x00FFFF22; "\nEXCEPTION: PAGE FAULT");
+                println!("Protection violation at {:#x} in {}", address, region.name);
+            },
+        }
+    } else {
+        println!(0x00FFFF22; "\nEXCEPTION: PAGE FAULT");
+        println!("Unhandled access to {:#x}", address);
+    }
+
+    report(stack_frame, error, address);
+    loop {
+        asm::hlt();
+    }
+}
+
+// Why a fault in a demand paged region couldn't be resolved
+enum DemandPageError {
+    // No frame was free to back the page
+    OutOfMemory,
+    // The frame allocator or the page tables stayed locked, most likely by the code that faulted
+    Locked,
+    Map(MapError),
+}
+
+// How many times the paging locks are tried before giving up. Both are IrqSpinLocks, so a holder on another CPU is
+// never preempted and lets go quickly, while a holder on this CPU is the code that faulted and never will
+const LOCK_ATTEMPTS: usize = 100000;
+
+// Tries a lock a bounded number of times, spinning on it could deadlock with the code that faulted
+fn try_lock_bounded<T>(lock: &IrqSpinLock<T>) -> Option<IrqSpinLockGuard<'_, T>> {
+    for _ in 0..LOCK_ATTEMPTS {
+        if let Some(guard) = lock.try_lock() {
+            return Some(guard);
+        }
+        core::hint::spin_loop();
+    }
+    return None;
+}
+
+// Backs the page containing address with a zeroed frame.
+fn map_demand_page(address: u64, flags: PageFlags) -> Result<(), DemandPageError> {
+    let frame = try_lock_bounded(&FRAME_ALLOCATOR)
+        .ok_or(DemandPageError::Locked)?
+        .alloc_frame()
+        .ok_or(DemandPageError::OutOfMemory)?;
+    unsafe {
+        core::ptr::write_bytes(phys_to_virt(frame) as *mut u8, 0, FRAME_SIZE as usize);
+    }
+
+    let page = address & !(FRAME_SIZE - 1);
+    let result = match try_lock_bounded(&PAGE_TABLE_MANAGER) {
+        Some(mut manager) => manager.map(page, frame, flags).map_err(|error| match error {
+            MapError::FrameAllocationFailed => DemandPageError::OutOfMemory,
+            error => DemandPageError::Map(error),
+        }),
+        None => Err(DemandPageError::Locked),
+    };
+    if result.is_err() {
+        // The allocator may be held by the code that faulted, losing one frame beats deadlocking on it
+        match try_lock_bounded(&FRAME_ALLOCATOR) {
+            Some(mut allocator) => allocator.free_frame(frame),
+            None => println!(0x00FF2222; "Frame allocator is locked, leaking frame {:#x}", frame),
+        }
+    }
+    return result;
+}
+
+// Prints where the fault happened and how the address translates.
+fn report(stack_frame: &ExceptionStackFrame, error: PageFaultErrorCode, address: u64) -> () {
+    println!("{:#?}", stack_frame);
+    println!("{:#?}", error);
+
+    let rip = stack_frame.instruction_pointer;
+    let (text_start, text_end) = unsafe { (&__text_start as *const u8 as u64, &__text_end as *const u8 as u64) };
+    if let Some((name, offset)) = symbols::lookup(rip) {
+        println!("Faulting instruction: {}+{:#x} ({:#x})", Demangle(name), offset, rip);
+    } else if rip >= text_start && rip < text_end {
+        println!("Faulting instruction: kernel .text+{:#x} ({:#x})", rip - text_start, rip);
+    } else {
+        println!("Faulting instruction: {:#x}, outside the kernel", rip);
+    }
+
+    let manager = match PAGE_TABLE_MANAGER.try_lock() {
+        Some(manager) => manager,
+        None => {
+            println!("Page tables are locked, can't walk {:#x}", address);
+            return;
+        }
+    };
+    let levels = ["PML4", "PDPT", "PD", "PT"];
+    for (level, entry) in manager.walk(address).iter().enumerate() {
+        if let Some(entry) = entry {
+            println!("{:>4}: {:#018x} address {:#x} flags {:#x}",
+                levels[level], entry.bits(), entry.address(), entry.flags().bits());
+        }
+    }
+}
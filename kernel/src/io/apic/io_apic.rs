@@ -0,0 +1,146 @@
+//! # IO-APIC
+//! 
+//! Routes device interrupts, identified by global system interrupt (GSI) number, to a vector on a local APIC through
+//! its redirection table.
+
+use crate::paging;
+use core::ptr::{read_volatile, write_volatile};
+
+/// Where the IO-APIC sits on almost every PC, used until ACPI says otherwise.
+pub const DEFAULT_IO_APIC_ADDRESS: u64 = 0xFEC0_0000;
+
+// The registers are accessed indirectly, select one through IOREGSEL then use IOWIN
+const IOREGSEL: u64 = 0x00;
+const IOWIN: u64 = 0x10;
+
+const IOAPICID: u32 = 0x00;
+const IOAPICVER: u32 = 0x01;
+const IOREDTBL: u32 = 0x10;
+
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+/// Electrical properties of an interrupt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trigger {
+    pub active_low: bool,
+    pub level_triggered: bool,
+}
+
+impl Trigger {
+    /// ISA interrupts are active high and edge triggered.
+    pub const ISA: Trigger = Trigger {
+        active_low: false,
+        level_triggered: false,
+    };
+}
+
+/// Errors from [`IoApic::redirect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectError {
+    /// The GSI is outside this IO-APIC's redirection table
+    UnhandledGsi(u32),
+}
+
+/// # IoApic
+/// 
+/// A single IO-APIC, handling the GSIs from `gsi_base` up to `gsi_base + redirection_entries()`.
+pub struct IoApic {
+    base: u64,
+    gsi_base: u32,
+}
+
+impl IoApic {
+    pub const fn new() -> IoApic {
+        IoApic { base: 0, gsi_base: 0 }
+    }
+
+    /// # Init
+    /// 
+    /// Maps the registers and masks every redirection entry.
+    /// 
+    /// ## Arguments
+    /// * 'physical_address' - address of the IO-APIC's registers
+    /// * 'gsi_base' - first GSI it handles
+    pub fn init(&mut self, physical_address: u64, gsi_base: u32) -> () {
+        self.base = paging::map_mmio(physical_address, 0x1000);
+        self.gsi_base = gsi_base;
+        for i in 0..self.redirection_entries() {
+            self.write_entry(i, REDIRECTION_MASKED);
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        ((self.read(IOAPICID) >> 24) & 0xF) as u8
+    }
+
+    /// Number of GSIs this IO-APIC handles.
+    pub fn redirection_entries(&self) -> u32 {
+        ((self.read(IOAPICVER) >> 16) & 0xFF) + 1
+    }
+
+    /// True if the GSI is one of this IO-APIC's.
+    pub fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.redirection_entries()
+    }
+
+    /// # Redirect
+    /// 
+    /// Delivers a GSI as `vector` to the local APIC `destination`, the entry starts unmasked.
+    /// 
+    /// ## Returns
+    /// * 'Result<(), RedirectError>' - [`RedirectError::UnhandledGsi`] if the GSI isn't one of this IO-APIC's, writing
+    ///   past the last redirection entry would hit some other register
+    pub fn redirect(&self, gsi: u32, vector: u8, destination: u8, trigger: Trigger) -> Result<(), RedirectError> {
+        if !self.handles(gsi) {
+            return Err(RedirectError::UnhandledGsi(gsi));
+        }
+        let mut entry = vector as u64 | (destination as u64) << 56;
+        if trigger.active_low {
+            entry |= REDIRECTION_ACTIVE_LOW;
+        }
+        if trigger.level_triggered {
+            entry |= REDIRECTION_LEVEL_TRIGGERED;
+        }
+        self.write_entry(gsi - self.gsi_base, entry);
+        return Ok(());
+    }
+
+    pub fn mask(&self, gsi: u32) -> () {
+        let entry = self.read_entry(gsi - self.gsi_base);
+        self.write_entry(gsi - self.gsi_base, entry | REDIRECTION_MASKED);
+    }
+
+    pub fn unmask(&self, gsi: u32) -> () {
+        let entry = self.read_entry(gsi - self.gsi_base);
+        self.write_entry(gsi - self.gsi_base, entry & !REDIRECTION_MASKED);
+    }
+
+    fn read_entry(&self, index: u32) -> u64 {
+        let low = self.read(IOREDTBL + index * 2) as u64;
+        let high = self.read(IOREDTBL + index * 2 + 1) as u64;
+        return low | high << 32;
+    }
+
+    fn write_entry(&self, index: u32, entry: u64) -> () {
+        // Mask first so the entry is never live half written
+        self.write(IOREDTBL + index * 2, REDIRECTION_MASKED as u32);
+        self.write(IOREDTBL + index * 2 + 1, (entry >> 32) as u32);
+        self.write(IOREDTBL + index * 2, entry as u32);
+    }
+
+    fn read(&self, register: u32) -> u32 {
+        unsafe {
+            write_volatile((self.base + IOREGSEL) as *mut u32, register);
+            read_volatile((self.base + IOWIN) as *const u32)
+        }
+    }
+
+    fn write(&self, register: u32, value: u32) -> () {
+        unsafe {
+            write_volatile((self.base + IOREGSEL) as *mut u32, register);
+            write_volatile((self.base + IOWIN) as *mut u32, value);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! # Local APIC
+//! 
+//! Every CPU has its own local APIC at the same physical address, it receives interrupts from the IO-APIC and other
+//! CPUs and needs an end of interrupt for each one it delivers.
+
+use crate::asm;
+use crate::paging;
+use core::ptr::{read_volatile, write_volatile};
+
+const IA32_APIC_BASE_MSR: u32 = 0x1B;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const APIC_BASE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+// Register offsets from the base address
+const ID: u64 = 0x20;
+const VERSION: u64 = 0x30;
+const TASK_PRIORITY: u64 = 0x80;
+const END_OF_INTERRUPT: u64 = 0xB0;
+const SPURIOUS_VECTOR: u64 = 0xF0;
+const ERROR_STATUS: u64 = 0x280;
+const INTERRUPT_COMMAND_LOW: u64 = 0x300;
+const INTERRUPT_COMMAND_HIGH: u64 = 0x310;
+const LVT_ERROR: u64 = 0x370;
+
+const SPURIOUS_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+
+const ICR_DELIVERY_INIT: u32 = 0b101 << 8;
+const ICR_DELIVERY_STARTUP: u32 = 0b110 << 8;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+const ICR_LEVEL_ASSERT: u32 = 1 << 14;
+
+/// Vector spurious interrupts are delivered on, its handler must not send an end of interrupt.
+pub const SPURIOUS_INTERRUPT_VECTOR: u8 = 0xFF;
+
+/// # LocalApic
+/// 
+/// The registers of the local APIC, the same address refers to each CPU's own local APIC.
+pub struct LocalApic {
+    base: u64,
+}
+
+impl LocalApic {
+    pub const fn new() -> LocalApic {
+        LocalApic { base: 0 }
+    }
+
+    /// The registers mapped at `base` by an earlier [`LocalApic::init`].
+    pub const fn from_base(base: u64) -> LocalApic {
+        LocalApic { base: base }
+    }
+
+    /// Virtual address of the registers, 0 before [`LocalApic::init`].
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// # Init
+    /// 
+    /// Sets the enable bit in the APIC base MSR, maps the registers and turns on the APIC through the spurious vector
+    /// register with every priority accepted.
+    pub fn init(&mut self) -> () {
+        let msr = asm::rdmsr(IA32_APIC_BASE_MSR) | APIC_BASE_ENABLE;
+        asm::wrmsr(IA32_APIC_BASE_MSR, msr);
+        let physical_base = msr & APIC_BASE_ADDRESS_MASK;
+        self.base = paging::map_mmio(physical_base, 0x1000);
+
+        self.enable();
+    }
+
+    /// Enables an already mapped local APIC, used by CPUs that start after [`LocalApic::init`].
+    pub fn enable(&self) -> () {
+        self.write(TASK_PRIORITY, 0);
+        self.write(LVT_ERROR, LVT_MASKED);
+        // The error status must be written before it is read
+        self.write(ERROR_STATUS, 0);
+        self.write(SPURIOUS_VECTOR, SPURIOUS_ENABLE | SPURIOUS_INTERRUPT_VECTOR as u32);
+    }
+
+    /// Signals the end of the interrupt being handled.
+    pub fn end_of_interrupt(&self) -> () {
+        self.write(END_OF_INTERRUPT, 0);
+    }
+
+    /// ID of the current CPU's local APIC.
+    pub fn id(&self) -> u8 {
+        (self.read(ID) >> 24) as u8
+    }
+
+    /// Sends an INIT IPI, which resets the CPU with local APIC `apic_id` into its wait for startup state.
+    pub fn send_init(&self, apic_id: u8) -> () {
+        self.send_ipi(apic_id, ICR_DELIVERY_INIT | ICR_LEVEL_ASSERT);
+    }
+
+    /// # Send startup
+    /// 
+    /// Sends a startup IPI, a CPU waiting after an INIT starts executing in real mode at `page` * 0x1000.
+    /// 
+    /// ## Arguments
+    /// * 'apic_id' - local APIC ID of the CPU to start
+    /// * 'page' - page number of the real mode entry point, it must be below 1 MiB
+    pub fn send_startup(&self, apic_id: u8, page: u8) -> () {
+        self.send_ipi(apic_id, ICR_DELIVERY_STARTUP | ICR_LEVEL_ASSERT | page as u32);
+    }
+
+    // Writing the low half of the interrupt command register sends the IPI, so the destination goes in first.
+    fn send_ipi(&self, apic_id: u8, command: u32) -> () {
+        self.write(INTERRUPT_COMMAND_HIGH, (apic_id as u32) << 24);
+        self.write(INTERRUPT_COMMAND_LOW, command);
+        while self.read(INTERRUPT_COMMAND_LOW) & ICR_DELIVERY_PENDING != 0 {
+            asm::nop();
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.read(VERSION) as u8
+    }
+
+    /// Reads a 32 bit register.
+    pub fn read(&self, register: u64) -> u32 {
+        unsafe { read_volatile((self.base + register) as *const u32) }
+    }
+
+    /// Writes a 32 bit register.
+    pub fn write(&self, register: u64, value: u32) -> () {
+        unsafe { write_volatile((self.base + register) as *mut u32, value) }
+    }
+}
@@ -0,0 +1,116 @@
+//! # APIC interrupt controllers
+//! 
+//! Replaces the 8259 PIC when the CPU has a local APIC. Device interrupts go through the [`IO_APIC`] to the
+//! [`LOCAL_APIC`] of the CPU that initialised it.
+//! 
+//! [`init`] / [`init_ap`]
+//! 
+//! [`route_isa_irq`]
+//! 
+//! [`end_of_interrupt`]
+
+pub mod io_apic;
+pub mod local;
+
+use crate::{acpi, asm, println};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use io_apic::{IoApic, Trigger, DEFAULT_IO_APIC_ADDRESS};
+use local::LocalApic;
+use crate::sync::IrqSpinLock;
+
+const ISA_IRQS: usize = 16;
+
+// Interrupt handlers signal the end of the interrupt through the local APIC
+pub static LOCAL_APIC: IrqSpinLock<LocalApic> = IrqSpinLock::new(LocalApic::new());
+pub static IO_APIC: IrqSpinLock<IoApic> = IrqSpinLock::new(IoApic::new());
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Where LOCAL_APIC's registers are mapped, it never changes after init so end_of_interrupt can skip the lock
+static LOCAL_APIC_BASE: AtomicU64 = AtomicU64::new(0);
+
+// The GSI and trigger mode each ISA IRQ is wired to. Without ACPI, ISA IRQs map to the same GSI apart from the PIT, which
+// is on pin 2 of the IO-APIC on nearly every PC
+static ISA_OVERRIDES: IrqSpinLock<[(u32, Trigger); ISA_IRQS]> = IrqSpinLock::new([
+    (2, Trigger::ISA), (1, Trigger::ISA), (2, Trigger::ISA), (3, Trigger::ISA),
+    (4, Trigger::ISA), (5, Trigger::ISA), (6, Trigger::ISA), (7, Trigger::ISA),
+    (8, Trigger::ISA), (9, Trigger::ISA), (10, Trigger::ISA), (11, Trigger::ISA),
+    (12, Trigger::ISA), (13, Trigger::ISA), (14, Trigger::ISA), (15, Trigger::ISA),
+]);
+
+/// Returns true if the CPU has a local APIC.
+pub fn is_supported() -> bool {
+    let mut eax: u32 = 1;
+    let mut ebx: u32 = 0;
+    let mut ecx: u32 = 0;
+    let mut edx: u32 = 0;
+    asm::cpuid(&mut eax, &mut ebx, &mut ecx, &mut edx);
+    return edx & (1 << 9) != 0;
+}
+
+/// Returns true once [`init`] has switched interrupt delivery to the APIC.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// # Init
+/// 
+/// Enables this CPU's local APIC and sets up the IO-APIC with every entry masked. The legacy PIC must already be
+/// masked, IRQs are then turned on one at a time with [`route_isa_irq`]. The IO-APIC and ISA wiring come from the
+/// ACPI MADT when there is one, only the IO-APIC handling GSI 0 is used.
+pub fn init() -> () {
+    let mut io_apic_address = DEFAULT_IO_APIC_ADDRESS;
+    if let Some(madt) = &acpi::ACPI.lock().madt {
+        if let Some(entry) = madt.io_apics.iter().find(|entry| entry.gsi_base == 0) {
+            io_apic_address = entry.address;
+        }
+        // Without an override an ISA IRQ is on the GSI with the same number
+        for irq in 0..ISA_IRQS as u8 {
+            set_isa_override(irq, irq as u32, Trigger::ISA);
+        }
+        for entry in madt.overrides.iter() {
+            set_isa_override(entry.irq, entry.gsi, Trigger {
+                active_low: entry.active_low,
+                level_triggered: entry.level_triggered,
+            });
+        }
+    }
+
+    let mut local = LOCAL_APIC.lock();
+    local.init();
+    LOCAL_APIC_BASE.store(local.base(), Ordering::Release);
+    let mut io = IO_APIC.lock();
+    io.init(io_apic_address, 0);
+    ENABLED.store(true, Ordering::Release);
+
+    println!(0x0022FF22; "-- Initialised local APIC {} (version {:#x}) and IO-APIC {} with {} entries",
+        local.id(), local.version(), io.id(), io.redirection_entries());
+}
+
+/// Enables the local APIC of an application processor, [`init`] must already have run on the BSP.
+pub fn init_ap() -> () {
+    LOCAL_APIC.lock().enable();
+}
+
+/// Replaces the GSI and trigger mode an ISA IRQ is routed through, from an ACPI interrupt source override.
+pub fn set_isa_override(irq: u8, gsi: u32, trigger: Trigger) -> () {
+    if (irq as usize) < ISA_IRQS {
+        ISA_OVERRIDES.lock()[irq as usize] = (gsi, trigger);
+    }
+}
+
+/// # Route ISA IRQ
+/// 
+/// Delivers a legacy IRQ to `vector` on the current CPU, following any interrupt source override.
+pub fn route_isa_irq(irq: u8, vector: u8) -> () {
+    let (gsi, trigger) = ISA_OVERRIDES.lock()[irq as usize];
+    let destination = LOCAL_APIC.lock().id();
+    if let Err(e) = IO_APIC.lock().redirect(gsi, vector, destination, trigger) {
+        println!(0x00FF2222; "Failed to route ISA IRQ {}: {:?}", irq, e);
+    }
+}
+
+/// Signals the end of an interrupt to the current CPU's local APIC. Only the register address is shared, so this
+/// doesn't take [`LOCAL_APIC`] and is safe from any interrupt handler.
+pub fn end_of_interrupt() -> () {
+    LocalApic::from_base(LOCAL_APIC_BASE.load(Ordering::Acquire)).end_of_interrupt();
+}
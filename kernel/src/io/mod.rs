@@ -1,20 +1,84 @@
 mod pic;
+pub mod apic;
 pub mod keyboard;
+pub mod pit;
+pub mod rtc;
+pub mod serial;
 
 use core::arch::asm;
-use pic::ChainedPIC;
+use pic::{ChainedPIC, PIC_MASTER_OFFSET};
 use keyboard::ps2::Ps2Controller;
-use spin::Mutex;
+use crate::sync::IrqSpinLock;
 
-pub static PIC: Mutex<ChainedPIC> = Mutex::new(ChainedPIC::new());
+// Both are used by interrupt handlers
+pub static PIC: IrqSpinLock<ChainedPIC> = IrqSpinLock::new(ChainedPIC::new());
 // make PS2 apart of the PIC struct?
-pub static PS2: Mutex<Ps2Controller> = Mutex::new(Ps2Controller::new());
+pub static PS2: IrqSpinLock<Ps2Controller> = IrqSpinLock::new(Ps2Controller::new());
 
 pub fn init_pic() -> () {
     let pic = PIC.lock();
     pic.remap();
-    // Enable keyboard interrupt
-    pic.set_interrupt_mask(0b11111101, 0b11111111);
+    // Enable timer and keyboard interrupts
+    pic.set_interrupt_mask(0b11111100, 0b11111111);
+}
+
+/// # Init interrupt controller
+/// 
+/// Remaps the legacy PIC away from the exception vectors, then switches to the APIC if the CPU has one. Either way IRQ
+/// n arrives on vector 32 + n and only the timer and keyboard are enabled.
+pub fn init_interrupt_controller() -> () {
+    init_pic();
+    if !apic::is_supported() {
+        crate::println!(0x0022FF22; "-- No APIC, using the 8259 PIC");
+        return;
+    }
+
+    // Masked PIC IRQs can still arrive as spurious interrupts, they land on the remapped vectors
+    PIC.lock().disable();
+    apic::init();
+    apic::route_isa_irq(0, PIC_MASTER_OFFSET);
+    apic::route_isa_irq(1, PIC_MASTER_OFFSET + 1);
+}
+
+/// Signals the end of an IRQ to whichever interrupt controller delivered it.
+pub fn end_of_interrupt(irq: u8) -> () {
+    if apic::is_enabled() {
+        apic::end_of_interrupt();
+    } else if irq >= 8 {
+        PIC.lock().end_slave();
+    } else {
+        PIC.lock().end_master();
+    }
+}
+
+/// Unmasks ISA `irq` on whichever interrupt controller is in use, it arrives on vector 32 + irq.
+pub fn enable_irq(irq: u8) -> () {
+    if apic::is_enabled() {
+        apic::route_isa_irq(irq, PIC_MASTER_OFFSET + irq);
+    } else {
+        PIC.lock().unmask(irq);
+    }
+}
+
+/// Starts the PIT ticking at [`pit::DEFAULT_FREQUENCY`], IRQ0 must be unmasked by [`init_pic`] for it to count.
+pub fn init_pit() -> () {
+    let frequency = pit::set_frequency(pit::DEFAULT_FREQUENCY);
+    crate::println!(0x0022FF22; "-- Initialised PIT at {} Hz", frequency);
+}
+
+/// Reads the boot time from the RTC and starts its periodic interrupt on IRQ 8.
+pub fn init_rtc() -> () {
+    rtc::init();
+    let frequency = rtc::enable_periodic(rtc::DEFAULT_RATE);
+    enable_irq(8);
+    crate::println!(0x0022FF22; "-- Initialised RTC, periodic interrupt at {} Hz", frequency);
+}
+
+/// Sets up [`serial::COM1`], output printed before this only reaches the screen.
+pub fn init_serial() -> () {
+    if serial::COM1.lock().init() {
+        crate::println!(0x0022FF22; "-- Initialised serial port COM1");
+    }
 }
 
 pub unsafe fn out_b( port: u16, value: u8) -> (){
@@ -88,6 +88,22 @@ impl ChainedPIC {
         self
     }
 
+    /// Unmasks a single IRQ, slave IRQs also unmask the cascade on IRQ 2.
+    pub fn unmask(&self, irq: u8) -> &Self {
+        if irq < 8 {
+            self.master.out_data(self.master.in_data() & !(1 << irq));
+        } else {
+            self.slave.out_data(self.slave.in_data() & !(1 << (irq - 8)));
+            self.master.out_data(self.master.in_data() & !(1 << 2));
+        }
+        self
+    }
+
+    /// Masks every IRQ, for when the APIC takes over.
+    pub fn disable(&self) -> &Self {
+        self.set_interrupt_mask(0xFF, 0xFF)
+    }
+
     pub fn end_master(&self) -> &Self {
         self.master.end();
         self
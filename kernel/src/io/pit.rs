@@ -0,0 +1,116 @@
+//! # 8253/8254 programmable interval timer
+//! 
+//! Channel 0 fires IRQ0 at a configurable frequency, every interrupt advances the system tick counter.
+//! 
+//! [`set_frequency`]
+//! 
+//! [`ticks`] / [`uptime`] / [`uptime_ms`]
+//! 
+//! [`sleep_ms`]
+//! 
+//! [`busy_wait_us`] polled delay on channel 2, for calibrating other clocks
+
+use crate::asm::{self, inb, outb};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::time::Duration;
+
+/// Frequency of the PIT's input clock in Hz.
+pub const PIT_FREQUENCY: u32 = 1193182;
+
+/// Tick rate set by [`super::init_pit`], one tick per millisecond.
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+const CHANNEL_0: u16 = 0x40;
+const CHANNEL_2: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+// Channel 2's gate and output are wired to the PC speaker control port
+const SPEAKER_PORT: u16 = 0x61;
+
+// Channel 0, lobyte/hibyte access, mode 3 square wave, binary
+const CHANNEL_0_SQUARE_WAVE: u8 = 0x36;
+// Channel 2, lobyte/hibyte access, mode 0 interrupt on terminal count, binary
+const CHANNEL_2_ONE_SHOT: u8 = 0xB0;
+
+const SPEAKER_GATE: u8 = 1 << 0;
+const SPEAKER_DATA: u8 = 1 << 1;
+const CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+/// # Set frequency
+/// 
+/// Programs channel 0 to interrupt `hz` times a second. The PIT can only divide its clock by a 16 bit value so the
+/// frequency is clamped to what it can produce, roughly 19 Hz up to [`PIT_FREQUENCY`].
+/// 
+/// ## Returns
+/// * 'u32' - The frequency actually set
+pub fn set_frequency(hz: u32) -> u32 {
+    let divisor = (PIT_FREQUENCY / hz.max(1)).clamp(1, 0xFFFF);
+    outb(COMMAND, CHANNEL_0_SQUARE_WAVE);
+    outb(CHANNEL_0, (divisor & 0xFF) as u8);
+    outb(CHANNEL_0, (divisor >> 8) as u8);
+
+    let actual = PIT_FREQUENCY / divisor;
+    FREQUENCY.store(actual, Ordering::Relaxed);
+    return actual;
+}
+
+/// Current tick rate in Hz, 0 until [`set_frequency`] has been called.
+pub fn frequency() -> u32 {
+    FREQUENCY.load(Ordering::Relaxed)
+}
+
+/// Advances the tick counter, called from the IRQ0 handler.
+pub fn tick() -> () {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of timer interrupts since the PIT was started.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Milliseconds since the PIT was started.
+pub fn uptime_ms() -> u64 {
+    let frequency = frequency() as u64;
+    if frequency == 0 {
+        return 0;
+    }
+    return ticks() * 1000 / frequency;
+}
+
+/// Time since the PIT was started.
+pub fn uptime() -> Duration {
+    Duration::from_millis(uptime_ms())
+}
+
+/// # Sleep
+/// 
+/// Halts until at least `ms` milliseconds have passed. Interrupts must be enabled, otherwise this never returns.
+pub fn sleep_ms(ms: u64) -> () {
+    let frequency = frequency() as u64;
+    // Round up so short sleeps still wait at least one tick
+    let target = ticks() + (ms * frequency + 999) / 1000;
+    while ticks() < target {
+        asm::hlt();
+    }
+}
+
+/// # Busy wait
+/// 
+/// Spins for `us` microseconds by polling channel 2, so it works with interrupts off and leaves channel 0's ticks alone.
+/// The count is 16 bits, waits are capped at about 54 ms.
+pub fn busy_wait_us(us: u32) -> () {
+    let count = (PIT_FREQUENCY as u64 * us as u64 / 1_000_000).clamp(1, 0xFFFF);
+    let speaker = inb(SPEAKER_PORT);
+    // Gate the channel on and keep the speaker disconnected
+    outb(SPEAKER_PORT, (speaker & !SPEAKER_DATA) | SPEAKER_GATE);
+    outb(COMMAND, CHANNEL_2_ONE_SHOT);
+    outb(CHANNEL_2, (count & 0xFF) as u8);
+    outb(CHANNEL_2, (count >> 8) as u8);
+
+    // The output goes high when the count reaches 0
+    while inb(SPEAKER_PORT) & CHANNEL_2_OUTPUT == 0 {}
+    outb(SPEAKER_PORT, speaker);
+}
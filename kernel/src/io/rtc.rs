@@ -0,0 +1,190 @@
+//! # CMOS real-time clock
+//!
+//! The battery backed clock read through the CMOS index and data ports. The date decoding lives in
+//! [`kernel_lib::time`].
+//!
+//! [`read`] the current date and time
+//!
+//! [`boot_time`] when the kernel started
+//!
+//! [`enable_periodic`] / [`disable_periodic`] the periodic interrupt on IRQ 8
+//!
+//! [`handle_interrupt`] / [`periodic_ticks`]
+
+use crate::{acpi, asm, println};
+use crate::interrupts::without_interrupts;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+pub use kernel_lib::time::DateTime;
+use kernel_lib::time::{bcd_to_binary, hour_from_12};
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0A;
+const REGISTER_STATUS_B: u8 = 0x0B;
+const REGISTER_STATUS_C: u8 = 0x0C;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_C_PERIODIC: u8 = 1 << 6;
+const HOUR_PM: u8 = 1 << 7;
+
+/// Frequency of the RTC's oscillator, the periodic interrupt divides it.
+pub const RTC_FREQUENCY: u32 = 32768;
+
+/// Rate set by [`super::init_rtc`], 2 Hz.
+pub const DEFAULT_RATE: u8 = 15;
+
+// Unix timestamp read by init, 0 until then
+static BOOT_TIME: AtomicU64 = AtomicU64::new(0);
+static PERIODIC_TICKS: AtomicU64 = AtomicU64::new(0);
+static PERIODIC_FREQUENCY: AtomicU32 = AtomicU32::new(0);
+
+fn read_register(register: u8) -> u8 {
+    asm::outb(CMOS_ADDRESS, register);
+    return asm::inb(CMOS_DATA);
+}
+
+fn write_register(register: u8, value: u8) -> () {
+    asm::outb(CMOS_ADDRESS, register);
+    asm::outb(CMOS_DATA, value);
+}
+
+// The registers in the order they're read: seconds, minutes, hours, day, month, year, century
+fn read_raw(century_register: u8) -> [u8; 7] {
+    while read_register(REGISTER_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {}
+    let century = if century_register != 0 { read_register(century_register) } else { 0 };
+    return [
+        read_register(REGISTER_SECONDS),
+        read_register(REGISTER_MINUTES),
+        read_register(REGISTER_HOURS),
+        read_register(REGISTER_DAY),
+        read_register(REGISTER_MONTH),
+        read_register(REGISTER_YEAR),
+        century,
+    ];
+}
+
+/// # Read
+///
+/// Reads the current date and time. The RTC can tick over in the middle of a read, so registers are read until two
+/// reads in a row agree.
+///
+/// ## Returns
+/// * 'DateTime' - the time the RTC holds, firmware normally keeps it in UTC
+pub fn read() -> DateTime {
+    // The FADT says which CMOS register has the century, without one the year is assumed to be 20xx
+    let century_register = acpi::ACPI.lock().fadt.map(|fadt| fadt.century).unwrap_or(0);
+
+    // Interrupts are off so the IRQ 8 handler can't change the selected register in the middle
+    let (raw, status_b) = without_interrupts(|| {
+        let mut raw = read_raw(century_register);
+        loop {
+            let again = read_raw(century_register);
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        (raw, read_register(REGISTER_STATUS_B))
+    });
+
+    let decode = |value: u8| if status_b & STATUS_B_BINARY != 0 { value } else { bcd_to_binary(value) };
+    let mut hour = decode(raw[2] & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hour = hour_from_12(hour, raw[2] & HOUR_PM != 0);
+    }
+    let century = if century_register != 0 { decode(raw[6]) as u16 } else { 20 };
+
+    return DateTime::new(
+        century * 100 + decode(raw[5]) as u16,
+        decode(raw[4]),
+        decode(raw[3]),
+        hour,
+        decode(raw[1]),
+        decode(raw[0]),
+    );
+}
+
+/// Records the boot time, called by [`super::init_rtc`].
+pub fn init() -> () {
+    let now = read();
+    if !now.is_valid() {
+        println!(0x00FF2222; "RTC holds an invalid time: {:?}", now);
+        return;
+    }
+    BOOT_TIME.store(now.unix_timestamp(), Ordering::Relaxed);
+    println!("Boot time: {}", now);
+}
+
+/// When the kernel started, None if the RTC couldn't be read.
+pub fn boot_time() -> Option<DateTime> {
+    match boot_timestamp() {
+        0 => None,
+        timestamp => Some(DateTime::from_unix_timestamp(timestamp)),
+    }
+}
+
+/// Unix timestamp of the boot, 0 if the RTC couldn't be read.
+pub fn boot_timestamp() -> u64 {
+    BOOT_TIME.load(Ordering::Relaxed)
+}
+
+/// # Enable periodic
+///
+/// Starts the periodic interrupt, IRQ 8 still has to be unmasked for it to arrive.
+///
+/// ## Arguments
+/// * 'rate' - divider from 3 to 15, the frequency is [`RTC_FREQUENCY`] >> (rate - 1) so 3 is 8192 Hz and 15 is 2 Hz
+///
+/// ## Returns
+/// * 'u32' - The frequency actually set
+pub fn enable_periodic(rate: u8) -> u32 {
+    let rate = rate.clamp(3, 15);
+    without_interrupts(|| {
+        let status_a = read_register(REGISTER_STATUS_A);
+        write_register(REGISTER_STATUS_A, (status_a & 0xF0) | rate);
+        let status_b = read_register(REGISTER_STATUS_B);
+        write_register(REGISTER_STATUS_B, status_b | STATUS_B_PERIODIC_INTERRUPT);
+        // An unread status C blocks further interrupts
+        read_register(REGISTER_STATUS_C);
+    });
+
+    let frequency = RTC_FREQUENCY >> (rate - 1);
+    PERIODIC_FREQUENCY.store(frequency, Ordering::Relaxed);
+    return frequency;
+}
+
+/// Stops the periodic interrupt.
+pub fn disable_periodic() -> () {
+    without_interrupts(|| {
+        let status_b = read_register(REGISTER_STATUS_B);
+        write_register(REGISTER_STATUS_B, status_b & !STATUS_B_PERIODIC_INTERRUPT);
+    });
+    PERIODIC_FREQUENCY.store(0, Ordering::Relaxed);
+}
+
+/// Acknowledges the interrupt by reading status C, called from the IRQ 8 handler.
+pub fn handle_interrupt() -> () {
+    if read_register(REGISTER_STATUS_C) & STATUS_C_PERIODIC != 0 {
+        PERIODIC_TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Number of periodic interrupts so far.
+pub fn periodic_ticks() -> u64 {
+    PERIODIC_TICKS.load(Ordering::Relaxed)
+}
+
+/// Current periodic interrupt frequency in Hz, 0 while it is off.
+pub fn periodic_frequency() -> u32 {
+    PERIODIC_FREQUENCY.load(Ordering::Relaxed)
+}
@@ -0,0 +1,97 @@
+//! # 16550 UART serial driver
+//! 
+//! [`SerialPort`] a single UART, polled rather than interrupt driven
+//! 
+//! [`COM1`] the first serial port, everything printed with print! and println! is mirrored to it
+
+use crate::asm::{inb, outb};
+use core::fmt;
+use crate::sync::IrqSpinLock;
+
+const COM1_PORT: u16 = 0x3F8;
+
+// Register offsets from the base port
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const LINE_CONTROL_DLAB: u8 = 0x80;
+const LINE_CONTROL_8N1: u8 = 0x03;
+const FIFO_ENABLE_CLEAR_14: u8 = 0xC7;
+const MODEM_DTR_RTS_OUT2: u8 = 0x0B;
+const MODEM_LOOPBACK: u8 = 0x1E;
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 0x20;
+
+// 115200 / 3 = 38400 baud
+const BAUD_DIVISOR: u16 = 3;
+
+/// # SerialPort
+/// 
+/// A 16550 compatible UART at an IO port. Writes are dropped until [`SerialPort::init`] has found a working UART, so 
+/// printing is safe on machines without one.
+pub struct SerialPort {
+    base: u16,
+    present: bool,
+}
+
+/// The first serial port, what qemu's `-serial stdio` connects to.
+pub static COM1: IrqSpinLock<SerialPort> = IrqSpinLock::new(SerialPort::new(COM1_PORT));
+
+impl SerialPort {
+    pub const fn new(base: u16) -> SerialPort {
+        SerialPort {
+            base: base,
+            present: false,
+        }
+    }
+
+    /// # Init
+    /// 
+    /// Sets the UART to 38400 baud 8N1 with FIFOs on and interrupts off, then checks it works by sending a byte through
+    /// loopback mode.
+    /// 
+    /// ## Returns
+    /// * 'bool' - true if a UART was found
+    pub fn init(&mut self) -> bool {
+        outb(self.base + INTERRUPT_ENABLE, 0x00);
+        outb(self.base + LINE_CONTROL, LINE_CONTROL_DLAB);
+        outb(self.base + DATA, (BAUD_DIVISOR & 0xFF) as u8);
+        outb(self.base + INTERRUPT_ENABLE, (BAUD_DIVISOR >> 8) as u8);
+        outb(self.base + LINE_CONTROL, LINE_CONTROL_8N1);
+        outb(self.base + FIFO_CONTROL, FIFO_ENABLE_CLEAR_14);
+
+        outb(self.base + MODEM_CONTROL, MODEM_LOOPBACK);
+        outb(self.base + DATA, 0xAE);
+        self.present = inb(self.base + DATA) == 0xAE;
+
+        outb(self.base + MODEM_CONTROL, MODEM_DTR_RTS_OUT2);
+        return self.present;
+    }
+
+    /// Sends a byte, waiting until the transmit buffer has room.
+    pub fn write_byte(&mut self, byte: u8) -> () {
+        if !self.present {
+            return;
+        }
+        while inb(self.base + LINE_STATUS) & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        outb(self.base + DATA, byte);
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // Terminals expect CRLF
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        return Ok(());
+    }
+}
@@ -5,66 +5,116 @@
 #![feature(once_cell)]
 #![feature(abi_x86_interrupt)]
 #![feature(exclusive_range_pattern)]
+#![feature(alloc_error_handler)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 #![allow(dead_code)]
 
+extern crate alloc;
+
+mod acpi;
 mod asm;
+mod clock;
 mod efi;
 mod gdt;
-mod math;
+mod heap;
 mod paging;
+mod percpu;
+mod power;
 mod print;
+mod smp;
+mod symbols;
+mod sync;
+mod task;
 mod interrupts;
 mod io;
+#[cfg(test)]
+mod testing;
 
+use kernel_lib::math;
 use print::Writer;
 use core::arch::asm;
 use crate::{gdt::{init_gdt}, interrupts::init_idt};
 
 extern "C"{
     fn set_interrupts() -> ();
+    fn switch_stack(stack_top: u64, entry: extern "C" fn(*const efi::BootInfo) -> !, argument: *const efi::BootInfo) -> !;
 }
 
+// Size of the stack kernel_main runs on
+const KERNEL_STACK_PAGES: u64 = 16;
+
+/// Entry point the bootloader jumps to.
+///
+/// ## Safety
+/// `boot_info` must point to the bootloader's [`efi::BootInfo`], which has to stay valid until
+/// [`paging::reclaim_boot_memory`].
 #[no_mangle]
-pub extern "C" fn _start(boot_info: *const efi::BootInfo) -> ! {
+pub unsafe extern "C" fn _start(boot_info: *const efi::BootInfo) -> ! {
     unsafe {
         Writer::init((*boot_info).glyph_buffer, (*boot_info).frame_buffer, false);
+        io::init_serial();
 
         println!("Hello, World!");
 
-        //paging::init_paging((*boot_info).memory_map, (*boot_info).memory_map_size, (*boot_info).descriptor_size);
+        efi::memory_map::init_memory_map(&*boot_info);
+        paging::init_frame_allocator();
+        paging::init_paging(&*boot_info);
+        heap::init_heap();
+
+        // Leave the firmware's stack for one with a guard page
+        let stack = paging::stack::alloc_stack(KERNEL_STACK_PAGES, "kernel main").expect("Failed to allocate the kernel stack");
+        switch_stack(stack.top, kernel_main, boot_info);
+    }
+}
 
+extern "C" fn kernel_main(boot_info: *const efi::BootInfo) -> ! {
+    unsafe {
+        percpu::init(0);
         init_gdt();
         init_idt();
+        acpi::init((*boot_info).rsdp);
+        efi::runtime::init((*boot_info).runtime_services);
+        symbols::init(&*boot_info);
+
+        // Off the boot stack and everything is copied out of the boot info, the bootloader's memory can go
+        paging::reclaim_boot_memory(&*boot_info);
+
+        #[cfg(test)]
+        test_main();
 
         // Do we want a microkernel? if so this should be a service.
-        io::init_pic();
+        io::init_interrupt_controller();
+        io::init_pit();
+        io::init_rtc();
+        clock::init();
+        smp::init();
+        task::init();
         set_interrupts();
 
+        let uptime = task::spawn("uptime", || {
+            task::sleep(clock::Duration::from_millis(100));
+            clock::uptime()
+        });
+        match uptime {
+            Ok(handle) => println!("Thread woke up at {:?}", handle.join()),
+            Err(e) => println!(0x00FF2222; "Failed to spawn a thread: {:?}", e),
+        }
+
         // Calls interrupt 0x03 - breakpoint
         asm!("INT 0x03");
-        
-        stack_overflow();
-
-        // Call div by zero interrupt (should not be possible after stack overflow)
-        asm!("int 0x0");
 
         println!("GoodBye, World!");
 
+        power::shutdown();
         loop {
-            asm::nop();
-            // asm::hlt();
+            asm::hlt();
         }
     }
 }
 
-
-// Taken from https://os.phil-opp.com/double-fault-exceptions/#kernel-stack-overflow
-#[allow(unconditional_recursion)]
-fn stack_overflow() {
-    stack_overflow(); // for each recursion, the return address is pushed
-    volatile::Volatile::new(0).read(); // prevent tail recursion optimizations
-}
-
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
     println!("Kenel panic!");
@@ -73,3 +123,9 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
         asm::hlt();
     }
 }
+
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    testing::test_panic_handler(info);
+}
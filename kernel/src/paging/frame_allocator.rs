@@ -0,0 +1,292 @@
+//! # Physical frame allocator
+//! 
+//! Keeps track of every 4 KiB physical frame described by the EFI memory map using a [`Bitmap`], where a set bit
+//! means the frame is either in use or reserved by the firmware/hardware. A second bitmap tells the reserved frames
+//! apart so they can never be freed.
+//! 
+//! [`FrameAllocator`]
+//! 
+//! [`FRAME_ALLOCATOR`]
+
+use kernel_lib::bitmap::Bitmap;
+use super::phys_to_virt;
+use crate::efi::memory_map::MemoryMap;
+use crate::math::RoundMath;
+use crate::sync::IrqSpinLock;
+
+/// Size of a single physical frame in bytes.
+pub const FRAME_SIZE: u64 = 0x1000;
+
+// Memory below 1 MiB is never handed out, it holds the real mode IVT, BIOS data and is needed for AP trampolines.
+const LOW_MEMORY_END: u64 = 0x100000;
+
+/// # FrameAllocator
+/// 
+/// Hands out physical frames. There is only ever one of these, use [`FRAME_ALLOCATOR`].
+/// 
+/// Memory is accounted in three buckets:
+/// - free: conventional memory that can be handed out
+/// - used: frames that have been handed out by [`FrameAllocator::alloc_frame`] or locked
+/// - reserved: frames the kernel must never touch, such as MMIO holes or firmware memory
+pub struct FrameAllocator {
+    bitmap: Bitmap,
+    // Set for frames that are reserved rather than used, placed right after the bitmap
+    reserved: Bitmap,
+    bitmap_address: u64,
+    free_memory: u64,
+    used_memory: u64,
+    reserved_memory: u64,
+    next_index: u64,
+}
+
+// The bitmap pointer is only ever accessed through the FRAME_ALLOCATOR lock.
+unsafe impl Send for FrameAllocator {}
+
+/// The global frame allocator, unusable until [`FrameAllocator::init`] has been called.
+pub static FRAME_ALLOCATOR: IrqSpinLock<FrameAllocator> = IrqSpinLock::new(FrameAllocator::new());
+
+impl FrameAllocator {
+    /// Creates an empty allocator with no frames.
+    pub const fn new() -> FrameAllocator {
+        FrameAllocator {
+            bitmap: Bitmap {
+                length: 0,
+                bitmap_ptr: core::ptr::null_mut(),
+            },
+            reserved: Bitmap {
+                length: 0,
+                bitmap_ptr: core::ptr::null_mut(),
+            },
+            bitmap_address: 0,
+            free_memory: 0,
+            used_memory: 0,
+            reserved_memory: 0,
+            next_index: 0,
+        }
+    }
+
+    /// # Init
+    /// 
+    /// Places the bitmap in the largest block of conventional memory and marks every frame that is not conventional 
+    /// memory as reserved.
+    /// 
+    /// ## Arguments
+    /// * 'memory_map' - the parsed EFI memory map
+    pub fn init(&mut self, memory_map: &MemoryMap) -> () {
+        // MMIO can sit far above the end of RAM so it is left out, otherwise the bitmap would cover a huge empty
+        // address range
+        let memory_end = memory_map.memory_end();
+        let largest = memory_map.iter()
+            .filter(|r| r.memory_type.is_usable())
+            .max_by_key(|r| r.pages)
+            .expect("No conventional memory in the memory map");
+        let largest_start = largest.start;
+        let largest_pages = largest.pages;
+
+        let frame_count = memory_end / FRAME_SIZE;
+        let bitmap_size = frame_count.ceil(8) / 8;
+        if bitmap_size * 2 > largest_pages * FRAME_SIZE {
+            panic!("No conventional memory region is large enough to hold the frame bitmaps");
+        }
+
+        // Everything starts reserved, free memory is then released from the map
+        self.bitmap_address = largest_start;
+        // The region is free conventional memory, locked for the bitmaps below
+        unsafe {
+            self.bitmap = Bitmap::new(phys_to_virt(largest_start) as *mut u8, bitmap_size);
+            self.reserved = Bitmap::new(phys_to_virt(largest_start + bitmap_size) as *mut u8, bitmap_size);
+        }
+        self.bitmap.fill(true);
+        self.reserved.fill(true);
+        self.free_memory = 0;
+        self.used_memory = 0;
+        self.reserved_memory = frame_count * FRAME_SIZE;
+        self.next_index = 0;
+
+        for region in memory_map.iter().filter(|r| r.memory_type.is_usable()) {
+            self.unreserve_frames(region.start, region.pages);
+        }
+
+        self.reserve_frames(0, LOW_MEMORY_END / FRAME_SIZE);
+        self.lock_frames(largest_start, (bitmap_size * 2).ceil(FRAME_SIZE) / FRAME_SIZE);
+    }
+
+    /// Points the bitmap at its current virtual address, must be called whenever [`phys_to_virt`] changes.
+    pub fn remap_bitmap(&mut self) -> () {
+        self.bitmap.bitmap_ptr = phys_to_virt(self.bitmap_address) as *mut u8;
+        self.reserved.bitmap_ptr = phys_to_virt(self.bitmap_address + self.bitmap.length) as *mut u8;
+    }
+
+    /// # Allocate frame
+    /// 
+    /// Finds a free frame, marks it as used and returns its physical address.
+    /// 
+    /// ## Returns
+    /// * 'Option<u64>' - The physical address of the frame, or None if physical memory is exhausted
+    pub fn alloc_frame(&mut self) -> Option<u64> {
+        let frame_count = self.frame_count();
+        for offset in 0..frame_count {
+            let index = (self.next_index + offset) % frame_count;
+            if !self.bitmap.get_bit(index) {
+                self.bitmap.set_bit(index);
+                self.free_memory -= FRAME_SIZE;
+                self.used_memory += FRAME_SIZE;
+                self.next_index = index + 1;
+                return Some(index * FRAME_SIZE);
+            }
+        }
+        return None;
+    }
+
+    /// # Allocate contiguous frames
+    /// 
+    /// Finds `count` physically contiguous free frames, marks them as used and returns the address of the first one.
+    /// Useful for DMA buffers and anything else that can't go through the page tables.
+    /// 
+    /// ## Returns
+    /// * 'Option<u64>' - The physical address of the first frame, or None if no run is long enough
+    pub fn alloc_contiguous(&mut self, count: u64) -> Option<u64> {
+        if count == 0 {
+            return None;
+        }
+
+        let frame_count = self.frame_count();
+        let mut run_start: u64 = 0;
+        let mut run_length: u64 = 0;
+        for index in 0..frame_count {
+            if self.bitmap.get_bit(index) {
+                run_length = 0;
+                continue;
+            }
+
+            if run_length == 0 {
+                run_start = index;
+            }
+            run_length += 1;
+
+            if run_length == count {
+                self.lock_frames(run_start * FRAME_SIZE, count);
+                return Some(run_start * FRAME_SIZE);
+            }
+        }
+        return None;
+    }
+
+    /// Returns a frame handed out by [`FrameAllocator::alloc_frame`] or [`FrameAllocator::alloc_contiguous`]. Free and
+    /// reserved frames are left alone.
+    pub fn free_frame(&mut self, address: u64) -> () {
+        let index = address / FRAME_SIZE;
+        if index >= self.frame_count() || !self.bitmap.get_bit(index) || self.reserved.get_bit(index) {
+            return;
+        }
+        self.bitmap.clear_bit(index);
+        self.free_memory += FRAME_SIZE;
+        self.used_memory -= FRAME_SIZE;
+        if index < self.next_index {
+            self.next_index = index;
+        }
+    }
+
+    /// Frees `count` frames starting at `address`.
+    pub fn free_frames(&mut self, address: u64, count: u64) -> () {
+        for i in 0..count {
+            self.free_frame(address + i * FRAME_SIZE);
+        }
+    }
+
+    /// Marks a free frame as used without going through [`FrameAllocator::alloc_frame`].
+    pub fn lock_frame(&mut self, address: u64) -> () {
+        let index = address / FRAME_SIZE;
+        if index >= self.frame_count() || self.bitmap.get_bit(index) {
+            return;
+        }
+        self.bitmap.set_bit(index);
+        self.free_memory -= FRAME_SIZE;
+        self.used_memory += FRAME_SIZE;
+    }
+
+    /// Marks `count` frames starting at `address` as used.
+    pub fn lock_frames(&mut self, address: u64, count: u64) -> () {
+        for i in 0..count {
+            self.lock_frame(address + i * FRAME_SIZE);
+        }
+    }
+
+    /// # Reclaim frames
+    /// 
+    /// Hands reserved frames back to the allocator, for memory the firmware or bootloader no longer needs. Frames below
+    /// 1 MiB stay reserved.
+    /// 
+    /// ## Returns
+    /// * 'u64' - The number of bytes that became free
+    pub fn reclaim_frames(&mut self, address: u64, count: u64) -> u64 {
+        let free_before = self.free_memory;
+        for i in 0..count {
+            let frame = address + i * FRAME_SIZE;
+            if frame >= LOW_MEMORY_END {
+                self.unreserve_frame(frame);
+            }
+        }
+        return self.free_memory - free_before;
+    }
+
+    // Marks a free frame as reserved, reserved frames are never handed out or freed.
+    fn reserve_frame(&mut self, address: u64) -> () {
+        let index = address / FRAME_SIZE;
+        if index >= self.frame_count() || self.bitmap.get_bit(index) {
+            return;
+        }
+        self.bitmap.set_bit(index);
+        self.reserved.set_bit(index);
+        self.free_memory -= FRAME_SIZE;
+        self.reserved_memory += FRAME_SIZE;
+    }
+
+    fn reserve_frames(&mut self, address: u64, count: u64) -> () {
+        for i in 0..count {
+            self.reserve_frame(address + i * FRAME_SIZE);
+        }
+    }
+
+    // Releases a reserved frame so it can be handed out, used frames are left alone.
+    fn unreserve_frame(&mut self, address: u64) -> () {
+        let index = address / FRAME_SIZE;
+        if index >= self.frame_count() || !self.reserved.get_bit(index) {
+            return;
+        }
+        self.bitmap.clear_bit(index);
+        self.reserved.clear_bit(index);
+        self.free_memory += FRAME_SIZE;
+        self.reserved_memory -= FRAME_SIZE;
+    }
+
+    fn unreserve_frames(&mut self, address: u64, count: u64) -> () {
+        for i in 0..count {
+            self.unreserve_frame(address + i * FRAME_SIZE);
+        }
+    }
+
+    fn frame_count(&self) -> u64 {
+        return self.bitmap.length * 8;
+    }
+
+    /// Bytes of memory that can still be allocated.
+    pub fn free_memory(&self) -> u64 {
+        return self.free_memory;
+    }
+
+    /// Bytes of memory that have been allocated or locked.
+    pub fn used_memory(&self) -> u64 {
+        return self.used_memory;
+    }
+
+    /// Bytes of memory that are unusable, including holes in the physical address space.
+    pub fn reserved_memory(&self) -> u64 {
+        return self.reserved_memory;
+    }
+
+    /// Bytes of physical address space covered by the allocator.
+    pub fn total_memory(&self) -> u64 {
+        return self.free_memory + self.used_memory + self.reserved_memory;
+    }
+}
@@ -1,125 +1,259 @@
-mod compatability_mode;
+//! # Physical memory management and paging
+//! 
+//! [`FRAME_ALLOCATOR`] hands out physical frames
+//! 
+//! [`PAGE_TABLE_MANAGER`] the kernel's address space, used to change mappings after boot
+//! 
+//! [`regions`] demand paged and guard regions, used by the page fault handler
+//! 
+//! [`stack`] kernel stacks with guard pages
+//! 
+//! [`map_mmio`] maps device registers uncached
+//! 
+//! [`reclaim_boot_memory`] frees the firmware and bootloader's memory once the kernel is done with it
+//! 
+//! [`phys_to_virt`] / [`virt_to_phys`] convert addresses through the physmap, a linear mapping of all physical RAM at
+//! [`PHYSMAP_OFFSET`]
 
-use crate::{asm, println};
-use crate::efi::EFI_MEMORY_DESCRIPTOR;
+pub mod frame_allocator;
+pub mod page_table;
+pub mod page_table_manager;
+pub mod regions;
+pub mod stack;
 
-macro_rules! EFI_CONVENTIONAL_MEMORY {
-    () => {7};
+use crate::{asm, print, println};
+use crate::efi::{BootInfo, MAX_KERNEL_SEGMENTS, SEGMENT_EXECUTABLE, SEGMENT_WRITABLE};
+use crate::efi::memory_map::{MemoryType, MEMORY_MAP};
+use crate::math::RoundMath;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::sync::IrqSpinLock;
+pub use frame_allocator::{FRAME_ALLOCATOR, FRAME_SIZE};
+pub use page_table::PageFlags;
+pub use page_table_manager::{PageTableManager, MapError, HUGE_PAGE_SIZE};
+
+/// The kernel's address space, held with interrupts off so the timer can't preempt whoever is changing it.
+/// 
+/// Mapping can allocate table frames, so the paging locks are always taken in the order [`PAGE_TABLE_MANAGER`],
+/// [`MEMORY_MAP`], [`FRAME_ALLOCATOR`].
+pub static PAGE_TABLE_MANAGER: IrqSpinLock<PageTableManager> = IrqSpinLock::new(PageTableManager::new());
+
+/// Virtual address physical address 0 is mapped at, the start of the higher half.
+pub const PHYSMAP_OFFSET: u64 = 0xFFFF_8000_0000_0000;
+
+// Offset currently added by phys_to_virt, 0 while the firmware's identity mapped tables are in use
+static PHYSMAP_BASE: AtomicU64 = AtomicU64::new(0);
+// End of the physical memory covered by the physmap
+static PHYSMAP_END: AtomicU64 = AtomicU64::new(0);
+
+const EFER_NXE: u64 = 1 << 11;
+const CR0_WP: u64 = 1 << 16;
+
+// Set once EFER.NXE is on, the NX bit is reserved and faults when it isn't
+static NX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Returns true if [`PageFlags::NO_EXECUTE`] is honoured by the CPU.
+pub fn nx_enabled() -> bool {
+    NX_ENABLED.load(Ordering::Relaxed)
 }
 
-pub fn init_paging(
-    memory_map: *const EFI_MEMORY_DESCRIPTOR,
-    memory_map_size: u64,
-    descriptor_size: u64,
-) -> () {
-    //clear cr4.pcide, must be done to disable paging
-    let cr4 = asm::read_cr4();
-    asm::write_cr4(cr4 & !(1 << 17));
-
-    //compatability mode must be entered to disable paging
-    compatability_mode::enter_compatibility_mode();
-
-    //disable paging
-    let cr0 = asm::read_cr0();
-    asm::write_cr0(cr0 & !(1 << 31));
-
-    println!("{}", cr0);
-
-    let mut l4_start:*mut u64 = core::ptr::null_mut();
-    let mut l3_start:*mut u64 = core::ptr::null_mut();
-    let mut l2_start:*mut u64 = core::ptr::null_mut();
-    let mut l1_start:*mut u64 = core::ptr::null_mut();
-
-    let mut l4_i: u64 = 0;
-    let mut l3_i: u64 = 0;
-    let mut l2_i: u64 = 0;
-    let mut l1_i: u64 = 0;
-
-    //iterate through efi memory map
-    for i in 0..memory_map_size / descriptor_size {
-        unsafe {
-            let descriptor: *const EFI_MEMORY_DESCRIPTOR = (memory_map as u64 + i * descriptor_size) as *const EFI_MEMORY_DESCRIPTOR;
-            
-            //if memory is not free
-            if (*descriptor).r#type != EFI_CONVENTIONAL_MEMORY!() {
-                continue;
-            }
+/// # Physical to virtual
+/// 
+/// Returns the virtual address physical memory can be accessed through. Before [`init_paging`] this is the physical
+/// address itself, the firmware identity maps everything, afterwards it is inside the physmap.
+pub fn phys_to_virt(physical_address: u64) -> u64 {
+    physical_address + PHYSMAP_BASE.load(Ordering::Relaxed)
+}
 
-            //if entire memory descriptor is in "low memory"
-            if (*descriptor).physical_start + (*descriptor).number_of_pages * 0x1000 < 0x1000000 {
-                continue;
-            }
+/// # Virtual to physical
+/// 
+/// Returns the physical address behind a virtual address. Physmap addresses are converted directly, anything else is
+/// looked up in the [`PAGE_TABLE_MANAGER`], so this must not be called while holding its lock.
+pub fn virt_to_phys(virtual_address: u64) -> Option<u64> {
+    let base = PHYSMAP_BASE.load(Ordering::Relaxed);
+    if base != 0 && virtual_address >= base && virtual_address - base < PHYSMAP_END.load(Ordering::Relaxed) {
+        return Some(virtual_address - base);
+    }
+    return PAGE_TABLE_MANAGER.lock().translate(virtual_address);
+}
 
-            //set physical_start and number of pages, efi memory descriptor could cross low memory boundry
-            let mut address:*mut u64 = (*descriptor).physical_start as *mut u64;
-            let mut pages:u64 = (*descriptor).number_of_pages;
-            if (*descriptor).physical_start < 0x1000000 {
-                address = 0x1000000 as *mut u64;
-                pages = (*descriptor).number_of_pages - ((0x1000000 - (*descriptor).physical_start) / 0x1000);
+/// Sets up the [`FRAME_ALLOCATOR`] from the [`MEMORY_MAP`], must be called before any frames are requested.
+pub fn init_frame_allocator() -> () {
+    let memory_map = MEMORY_MAP.lock();
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    allocator.init(&memory_map);
+    println!(0x0022FF22; "-- Initialised frame allocator");
+    println!("Free memory: {} KiB", allocator.free_memory() / 1024);
+    println!("Used memory: {} KiB", allocator.used_memory() / 1024);
+    println!("Reserved memory: {} KiB", allocator.reserved_memory() / 1024);
+}
+
+/// # Init paging
+/// 
+/// Builds the kernel's own page tables and switches CR3 to them.
+/// - all physical RAM and the framebuffer are mapped into the physmap at [`PHYSMAP_OFFSET`]
+/// - the kernel segments are mapped at the higher half addresses they were linked at, with permissions taken from
+///   their elf flags
+/// - everything in the memory map is also identity mapped, the boot stack and the boot info still live there
+/// - UEFI runtime regions are in the physmap too, the bootloader moved runtime services there. Runtime code is left
+///   executable and runtime MMIO is mapped uncached
+/// 
+/// ## Arguments
+/// * 'boot_info' - the boot info from the bootloader, the [`MEMORY_MAP`] must already have been read from it
+pub fn init_paging(boot_info: &BootInfo) -> () {
+    enable_protection_bits();
+
+    let mut manager = PAGE_TABLE_MANAGER.lock();
+    manager.init().expect("Failed to allocate the PML4");
+
+    let identity_flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+    let physmap_flags = PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::GLOBAL | PageFlags::NO_EXECUTE;
+    let mut physmap_end: u64 = 0;
+    for region in MEMORY_MAP.lock().iter() {
+        manager.map_range(region.start, region.start, region.pages, identity_flags)
+            .expect("Failed to identity map memory");
+
+        if !region.memory_type.is_mmio() {
+            let flags = match region.memory_type {
+                MemoryType::RuntimeServicesCode => physmap_flags.without(PageFlags::NO_EXECUTE),
+                _ => physmap_flags,
+            };
+            map_physmap(&mut manager, region.start, region.end(), flags);
+            if region.end() > physmap_end {
+                physmap_end = region.end();
             }
+        } else if region.is_runtime() {
+            manager.map_range(PHYSMAP_OFFSET + region.start, region.start, region.pages, physmap_flags | PageFlags::CACHE_DISABLE)
+                .expect("Failed to map runtime MMIO");
+        }
+    }
+
+    for segment in boot_info.kernel_segments() {
+        let mut flags = PageFlags::PRESENT | PageFlags::GLOBAL;
+        if segment.flags & SEGMENT_WRITABLE != 0 {
+            flags |= PageFlags::WRITABLE;
+        }
+        if segment.flags & SEGMENT_EXECUTABLE == 0 {
+            flags |= PageFlags::NO_EXECUTE;
+        }
+        manager.map_range(segment.virtual_start, segment.physical_start, segment.size / FRAME_SIZE, flags)
+            .expect("Failed to map the kernel");
+    }
+
+    let frame_buffer = print::framebuffer();
+    let start = (frame_buffer.base_address as u64).floor(FRAME_SIZE);
+    let end = (frame_buffer.base_address as u64 + frame_buffer.buffer_size).ceil(FRAME_SIZE);
+    for page in (start..end).step_by(FRAME_SIZE as usize) {
+        // The framebuffer may overlap a region that was already mapped
+        match manager.map(page, page, identity_flags | PageFlags::WRITE_THROUGH) {
+            Ok(()) | Err(MapError::AlreadyMapped) => {},
+            Err(e) => panic!("Failed to map the framebuffer: {:?}", e),
+        }
+        match manager.map(PHYSMAP_OFFSET + page, page, physmap_flags | PageFlags::WRITE_THROUGH) {
+            Ok(()) | Err(MapError::AlreadyMapped) | Err(MapError::InsideHugePage) => {},
+            Err(e) => panic!("Failed to map the framebuffer: {:?}", e),
+        }
+    }
+    unsafe { manager.load(); }
 
-            //iterate through pages in the descriptor
-            for _ in 0..pages {
-                //initialise first l4, l3, l2 and l1
-                if l4_start == core::ptr::null_mut() {
-                    l4_start = address;
-                }
-                else if l3_start == core::ptr::null_mut() {
-                    l3_start = address;
-                }
-                else if l2_start == core::ptr::null_mut() {
-                    l2_start = address;
-                }
-                else if l1_start == core::ptr::null_mut() {
-                    l1_start = address;
-                }
-
-                //if there is no more room to map pages
-                else if l4_i == 512 {
-                    return;
-                }
-
-                //create new paging tables if needed
-                //get new address for page table
-                //reset index to 0
-                //add address in parent table
-                //iterate parent table index
-                else if l3_i == 512 {
-                    l3_start = address;
-                    l3_i = 0;
-                    *(l4_start.offset(l4_i as isize)) = l3_start as u64;
-                    l4_i += 1;
-                }
-                else if l2_i == 512 {
-                    l2_start = address;
-                    l2_i = 0;
-                    *(l3_start.offset(l3_i as isize)) = l2_start as u64;
-                    l2_i += 1;
-                }
-                else if l1_i == 512 {
-                    l1_start = address;
-                    l1_i = 0;
-                    *(l2_start.offset(l2_i as isize)) = l1_start as u64;
-                    l2_i += 1;
-                }
-
-                //if none of the above we can assign a new virtual address
-                else {
-                    *(l1_start.offset(l1_i as isize)) = address as u64;
-                    l1_i += 1; 
-                }
-
-                address = address.offset(512); //1 page is 512 u64's
+    // Page tables and the frame bitmap are only reachable through the physmap from here on
+    PHYSMAP_END.store(physmap_end, Ordering::Relaxed);
+    PHYSMAP_BASE.store(PHYSMAP_OFFSET, Ordering::Relaxed);
+    FRAME_ALLOCATOR.lock().remap_bitmap();
+    println!(0x0022FF22; "-- Loaded kernel page tables, physmap covers {} MiB", physmap_end / 0x100000);
+}
+
+/// # Map MMIO
+/// 
+/// Maps device registers into the physmap as uncached memory. MMIO is left out of the physmap by [`init_paging`] since
+/// RAM is mapped write-back, so drivers must call this before touching their registers. Registers that ended up in the
+/// physmap anyway, inside a RAM region or one of its 2 MiB pages, have their pages made uncached. Must be called after
+/// [`init_paging`].
+/// 
+/// ## Arguments
+/// * 'physical_address' - start of the registers
+/// * 'size' - size of the registers in bytes
+/// 
+/// ## Returns
+/// * 'u64' - The virtual address of `physical_address`
+pub fn map_mmio(physical_address: u64, size: u64) -> u64 {
+    let flags = PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::WRITE_THROUGH | PageFlags::CACHE_DISABLE
+        | PageFlags::GLOBAL | PageFlags::NO_EXECUTE;
+    let start = physical_address.floor(FRAME_SIZE);
+    let end = (physical_address + size).ceil(FRAME_SIZE);
+
+    assert!(PHYSMAP_BASE.load(Ordering::Relaxed) == PHYSMAP_OFFSET, "MMIO mapped before init_paging");
+
+    let mut manager = PAGE_TABLE_MANAGER.lock();
+    for page in (start..end).step_by(FRAME_SIZE as usize) {
+        let virtual_address = PHYSMAP_OFFSET + page;
+        let result = match manager.map(virtual_address, page, flags) {
+            // The physmap already covers the page, probably write-back, so only the flags change
+            Err(MapError::AlreadyMapped) => manager.set_flags(virtual_address, flags),
+            Err(MapError::InsideHugePage) => manager.split_huge_page(virtual_address)
+                .and_then(|()| manager.set_flags(virtual_address, flags)),
+            result => result,
+        };
+        if let Err(e) = result {
+            panic!("Failed to map MMIO at {:#x}: {:?}", page, e);
+        }
+    }
+    return PHYSMAP_OFFSET + physical_address;
+}
+
+/// # Reclaim boot memory
+/// 
+/// Gives the memory used by the firmware's boot services and the bootloader back to the [`FRAME_ALLOCATOR`], the
+/// kernel segments are loader data too so they are skipped. Everything the kernel needs from the [`BootInfo`] must have
+/// been copied out first and the boot stack must have been left, the boot info itself lives on it.
+/// 
+/// ## Arguments
+/// * 'boot_info' - the boot info from the bootloader, it is invalid once this returns
+pub fn reclaim_boot_memory(boot_info: &BootInfo) -> () {
+    let mut segments = [(0u64, 0u64); MAX_KERNEL_SEGMENTS];
+    let segment_count = boot_info.kernel_segments().len();
+    for (i, segment) in boot_info.kernel_segments().iter().enumerate() {
+        segments[i] = (segment.physical_start, segment.physical_start + segment.size);
+    }
+    let is_kernel = |frame: u64| segments[..segment_count].iter().any(|&(start, end)| frame >= start && frame < end);
+
+    let memory_map = MEMORY_MAP.lock();
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    let mut reclaimed: u64 = 0;
+    for region in memory_map.iter().filter(|r| r.memory_type.is_reclaimable()) {
+        for frame in (region.start..region.end()).step_by(FRAME_SIZE as usize) {
+            if !is_kernel(frame) {
+                reclaimed += allocator.reclaim_frames(frame, 1);
             }
         }
     }
-;    //enable paging
-    asm::write_cr3(l4_start as u64);
-    asm::write_cr0(cr0);
+    println!(0x0022FF22; "-- Reclaimed {} KiB of boot memory", reclaimed / 1024);
+}
+
+// Maps physical memory from start to end into the physmap, using 2 MiB pages wherever they fit.
+fn map_physmap(manager: &mut PageTableManager, start: u64, end: u64, flags: PageFlags) -> () {
+    let mut address = start;
+    while address < end {
+        if address % HUGE_PAGE_SIZE == 0 && address + HUGE_PAGE_SIZE <= end {
+            manager.map_huge(PHYSMAP_OFFSET + address, address, flags).expect("Failed to map the physmap");
+            address += HUGE_PAGE_SIZE;
+        } else {
+            manager.map(PHYSMAP_OFFSET + address, address, flags).expect("Failed to map the physmap");
+            address += FRAME_SIZE;
+        }
+    }
+}
 
-    //return to long mode
-    compatability_mode::exit_compatibility_mode();
+// Turns on no-execute support when the CPU has it and makes ring 0 respect read only pages.
+fn enable_protection_bits() -> () {
+    let mut eax: u32 = 0x80000001;
+    let mut ebx: u32 = 0;
+    let mut ecx: u32 = 0;
+    let mut edx: u32 = 0;
+    asm::cpuid(&mut eax, &mut ebx, &mut ecx, &mut edx);
 
-    //set cr4.pcide if it was set before
-    asm::write_cr4(cr4);
+    if edx & (1 << 20) != 0 {
+        asm::write_efer(asm::read_efer() | EFER_NXE);
+        NX_ENABLED.store(true, Ordering::Relaxed);
+    }
+    asm::write_cr0(asm::read_cr0() | CR0_WP);
 }
@@ -0,0 +1,148 @@
+//! # x86_64 page table types
+//! 
+//! [`PageFlags`] the flag bits of a page table entry
+//! 
+//! [`PageTableEntry`] a single entry in any level of page table
+//! 
+//! [`PageTable`] a 4 KiB table of 512 entries, used for the PML4, PDPT, PD and PT alike
+
+use core::ops::{BitOr, BitOrAssign};
+
+/// # PageFlags
+/// 
+/// The flag bits of a [`PageTableEntry`]. Combine them with `|`.
+/// 
+/// ```
+/// manager.map(virt, phys, PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFlags(u64);
+
+impl PageFlags {
+    /// The entry points to a frame or table.
+    pub const PRESENT: PageFlags = PageFlags(1 << 0);
+    /// Writes are allowed, otherwise the page is read only.
+    pub const WRITABLE: PageFlags = PageFlags(1 << 1);
+    /// Ring 3 may access the page.
+    pub const USER: PageFlags = PageFlags(1 << 2);
+    /// Writes go straight to memory instead of the cache.
+    pub const WRITE_THROUGH: PageFlags = PageFlags(1 << 3);
+    /// The page is never cached, needed for MMIO.
+    pub const CACHE_DISABLE: PageFlags = PageFlags(1 << 4);
+    /// Set by the CPU when the page is accessed.
+    pub const ACCESSED: PageFlags = PageFlags(1 << 5);
+    /// Set by the CPU when the page is written to.
+    pub const DIRTY: PageFlags = PageFlags(1 << 6);
+    /// The entry maps a 2 MiB or 1 GiB page instead of pointing to a table.
+    pub const HUGE: PageFlags = PageFlags(1 << 7);
+    /// The TLB entry survives CR3 reloads, only for mappings shared by every address space.
+    pub const GLOBAL: PageFlags = PageFlags(1 << 8);
+    /// Instructions can't be fetched from the page, requires EFER.NXE.
+    pub const NO_EXECUTE: PageFlags = PageFlags(1 << 63);
+
+    /// No flags set.
+    pub const fn empty() -> PageFlags {
+        PageFlags(0)
+    }
+
+    /// The raw flag bits.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if every flag in `other` is also set in self.
+    pub const fn contains(&self, other: PageFlags) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    /// Returns self with the flags in `other` cleared.
+    pub const fn without(&self, other: PageFlags) -> PageFlags {
+        PageFlags(self.0 & !other.0)
+    }
+}
+
+impl BitOr for PageFlags {
+    type Output = PageFlags;
+
+    fn bitor(self, rhs: PageFlags) -> PageFlags {
+        PageFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PageFlags {
+    fn bitor_assign(&mut self, rhs: PageFlags) -> () {
+        self.0 |= rhs.0;
+    }
+}
+
+// Bits 12-51 of an entry hold the physical address of the frame or next table
+const ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const FLAGS_MASK: u64 = !ADDRESS_MASK;
+
+/// # PageTableEntry
+/// 
+/// A single 64 bit entry, the physical address in bits 12-51 and the [`PageFlags`] in the rest.
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct PageTableEntry(u64);
+
+impl PageTableEntry {
+    /// An entry with no address and no flags, not present.
+    pub const fn empty() -> PageTableEntry {
+        PageTableEntry(0)
+    }
+
+    /// The physical address the entry points to.
+    pub fn address(&self) -> u64 {
+        self.0 & ADDRESS_MASK
+    }
+
+    pub fn flags(&self) -> PageFlags {
+        PageFlags(self.0 & FLAGS_MASK)
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.flags().contains(PageFlags::PRESENT)
+    }
+
+    pub fn is_huge(&self) -> bool {
+        self.flags().contains(PageFlags::HUGE)
+    }
+
+    /// Points the entry at `address` with `flags`, `address` must be 4 KiB aligned.
+    pub fn set(&mut self, address: u64, flags: PageFlags) -> () {
+        self.0 = (address & ADDRESS_MASK) | flags.bits();
+    }
+
+    /// Adds `flags` to the entry without changing the address.
+    pub fn add_flags(&mut self, flags: PageFlags) -> () {
+        self.0 |= flags.bits();
+    }
+
+    pub fn clear(&mut self) -> () {
+        self.0 = 0;
+    }
+
+    /// The raw entry.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+/// # PageTable
+/// 
+/// One level of the page table hierarchy, always exactly one frame in size.
+#[repr(C, align(4096))]
+pub struct PageTable {
+    pub entries: [PageTableEntry; 512],
+}
+
+/// Splits a virtual address into its PML4, PDPT, PD and PT indices.
+pub fn table_indices(virtual_address: u64) -> [usize; 4] {
+    [
+        ((virtual_address >> 39) & 0x1FF) as usize,
+        ((virtual_address >> 30) & 0x1FF) as usize,
+        ((virtual_address >> 21) & 0x1FF) as usize,
+        ((virtual_address >> 12) & 0x1FF) as usize,
+    ]
+}
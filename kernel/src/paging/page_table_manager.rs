@@ -0,0 +1,348 @@
+//! # Four level page table manager
+//! 
+//! [`PageTableManager`] owns a PML4 and maps, unmaps and translates 4 KiB pages within it.
+//! 
+//! [`MapError`]
+
+use super::frame_allocator::{FRAME_ALLOCATOR, FRAME_SIZE};
+use super::page_table::{table_indices, PageFlags, PageTable, PageTableEntry};
+use super::phys_to_virt;
+use crate::asm;
+
+/// Size of a page mapped with [`PageTableManager::map_huge`].
+pub const HUGE_PAGE_SIZE: u64 = 0x200000;
+
+/// Reasons a mapping can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// No physical frame was free for an intermediate table.
+    FrameAllocationFailed,
+    /// The virtual page is already mapped, unmap it first.
+    AlreadyMapped,
+    /// The virtual page is covered by a 2 MiB or 1 GiB page.
+    InsideHugePage,
+    /// The virtual or physical address is not page aligned.
+    Unaligned,
+    /// The virtual page has no mapping to change.
+    NotMapped,
+}
+
+/// # PageTableManager
+/// 
+/// Manages one address space. Intermediate tables are allocated from the [`FRAME_ALLOCATOR`] as they are needed.
+/// 
+/// ## Example
+/// ```
+/// let mut manager = PAGE_TABLE_MANAGER.lock();
+/// manager.map(0xFFFF_A000_0000_0000, frame, PageFlags::PRESENT | PageFlags::WRITABLE)?;
+/// assert_eq!(manager.translate(0xFFFF_A000_0000_0123), Some(frame + 0x123));
+/// ```
+pub struct PageTableManager {
+    pml4: u64,
+}
+
+impl PageTableManager {
+    /// Creates a manager without a PML4, [`PageTableManager::init`] must be called before it is used.
+    pub const fn new() -> PageTableManager {
+        PageTableManager { pml4: 0 }
+    }
+
+    /// Allocates an empty PML4 for this address space.
+    pub fn init(&mut self) -> Result<(), MapError> {
+        self.pml4 = alloc_table()?;
+        return Ok(());
+    }
+
+    /// Physical address of the PML4, the value that gets written to CR3.
+    pub fn pml4_address(&self) -> u64 {
+        self.pml4
+    }
+
+    /// Switches the CPU to this address space.
+    /// 
+    /// ## Safety
+    /// The currently executing code, stack and any data in use must be mapped at the same addresses.
+    pub unsafe fn load(&self) -> () {
+        asm::write_cr3(self.pml4);
+    }
+
+    /// # Map
+    /// 
+    /// Maps the 4 KiB page at `virtual_address` to the frame at `physical_address`.
+    /// 
+    /// ## Arguments
+    /// * 'virtual_address' - page aligned virtual address
+    /// * 'physical_address' - frame aligned physical address
+    /// * 'flags' - the [`PageFlags`] of the final entry, [`PageFlags::PRESENT`] is always added
+    pub fn map(&mut self, virtual_address: u64, physical_address: u64, flags: PageFlags) -> Result<(), MapError> {
+        if virtual_address % FRAME_SIZE != 0 || physical_address % FRAME_SIZE != 0 {
+            return Err(MapError::Unaligned);
+        }
+
+        // Without EFER.NXE the NX bit is reserved, so it is silently dropped
+        let flags = if super::nx_enabled() { flags } else { flags.without(PageFlags::NO_EXECUTE) };
+
+        let table = self.create_tables(virtual_address, 3, flags)?;
+        let entry = &mut table.entries[table_indices(virtual_address)[3]];
+        if entry.is_present() {
+            return Err(MapError::AlreadyMapped);
+        }
+        entry.set(physical_address, flags | PageFlags::PRESENT);
+        return Ok(());
+    }
+
+    /// # Map huge
+    /// 
+    /// Maps the 2 MiB page at `virtual_address` to the 2 MiB of physical memory at `physical_address`. Fewer tables
+    /// and TLB entries are needed than for 512 small pages, so large linear mappings should use this.
+    /// 
+    /// ## Arguments
+    /// * 'virtual_address' - 2 MiB aligned virtual address
+    /// * 'physical_address' - 2 MiB aligned physical address
+    /// * 'flags' - the [`PageFlags`] of the entry, [`PageFlags::PRESENT`] and [`PageFlags::HUGE`] are always added
+    pub fn map_huge(&mut self, virtual_address: u64, physical_address: u64, flags: PageFlags) -> Result<(), MapError> {
+        if virtual_address % HUGE_PAGE_SIZE != 0 || physical_address % HUGE_PAGE_SIZE != 0 {
+            return Err(MapError::Unaligned);
+        }
+        let flags = if super::nx_enabled() { flags } else { flags.without(PageFlags::NO_EXECUTE) };
+
+        let table = self.create_tables(virtual_address, 2, flags)?;
+        let entry = &mut table.entries[table_indices(virtual_address)[2]];
+        if entry.is_present() {
+            return Err(MapError::AlreadyMapped);
+        }
+        entry.set(physical_address, flags | PageFlags::PRESENT | PageFlags::HUGE);
+        return Ok(());
+    }
+
+    /// Maps `count` consecutive pages starting at `virtual_address` to consecutive frames at `physical_address`.
+    pub fn map_range(
+        &mut self,
+        virtual_address: u64,
+        physical_address: u64,
+        count: u64,
+        flags: PageFlags,
+    ) -> Result<(), MapError> {
+        for i in 0..count {
+            self.map(virtual_address + i * FRAME_SIZE, physical_address + i * FRAME_SIZE, flags)?;
+        }
+        return Ok(());
+    }
+
+    /// # Set flags
+    /// 
+    /// Changes the flags of the 4 KiB mapping of `virtual_address`, keeping the frame it maps to, and invalidates its
+    /// TLB entry. Pages inside a huge page have to be split off with [`PageTableManager::split_huge_page`] first.
+    /// 
+    /// ## Arguments
+    /// * 'virtual_address' - any address in the page
+    /// * 'flags' - the new [`PageFlags`], [`PageFlags::PRESENT`] is always added
+    pub fn set_flags(&mut self, virtual_address: u64, flags: PageFlags) -> Result<(), MapError> {
+        let flags = if super::nx_enabled() { flags } else { flags.without(PageFlags::NO_EXECUTE) };
+        let entry = self.leaf_entry(virtual_address).ok_or(MapError::NotMapped)?;
+        let physical_address = entry.address();
+        entry.set(physical_address, flags | PageFlags::PRESENT);
+        asm::invlpg(virtual_address);
+        return Ok(());
+    }
+
+    /// # Split huge page
+    /// 
+    /// Replaces the 2 MiB page containing `virtual_address` with a table of 512 4 KiB pages that map the same memory
+    /// with the same flags, so part of it can be given other flags. Does nothing if the address is already mapped with
+    /// 4 KiB pages.
+    /// 
+    /// ## Returns
+    /// * 'Result<(), MapError>' - [`MapError::NotMapped`] if nothing maps the address, [`MapError::InsideHugePage`] if
+    ///   it is inside a 1 GiB page, which isn't split
+    pub fn split_huge_page(&mut self, virtual_address: u64) -> Result<(), MapError> {
+        let indices = table_indices(virtual_address);
+        let mut address = self.pml4;
+        for level in 0..2 {
+            let entry = self.table(address).entries[indices[level]];
+            if !entry.is_present() {
+                return Err(MapError::NotMapped);
+            }
+            if entry.is_huge() {
+                return Err(MapError::InsideHugePage);
+            }
+            address = entry.address();
+        }
+
+        let huge_entry = self.table(address).entries[indices[2]];
+        if !huge_entry.is_present() {
+            return Err(MapError::NotMapped);
+        }
+        if !huge_entry.is_huge() {
+            return Ok(());
+        }
+
+        let table = alloc_table()?;
+        let flags = huge_entry.flags().without(PageFlags::HUGE);
+        for (i, entry) in self.table_mut(table).entries.iter_mut().enumerate() {
+            entry.set(huge_entry.address() + i as u64 * FRAME_SIZE, flags);
+        }
+        // Like the tables from create_tables, the new entries decide the real permissions
+        let mut table_flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+        if flags.contains(PageFlags::USER) {
+            table_flags |= PageFlags::USER;
+        }
+        self.table_mut(address).entries[indices[2]].set(table, table_flags);
+        // Invalidating any address inside the huge page drops its TLB entry
+        asm::invlpg(virtual_address);
+        return Ok(());
+    }
+
+    /// # Unmap
+    /// 
+    /// Removes the mapping for the page containing `virtual_address` and invalidates its TLB entry. The frame is not
+    /// freed, that is up to the caller.
+    /// 
+    /// ## Returns
+    /// * 'Option<u64>' - The physical address the page was mapped to, None if it wasn't mapped
+    pub fn unmap(&mut self, virtual_address: u64) -> Option<u64> {
+        let entry = self.leaf_entry(virtual_address)?;
+        let physical_address = entry.address();
+        entry.clear();
+        asm::invlpg(virtual_address);
+        return Some(physical_address);
+    }
+
+    /// # Translate
+    /// 
+    /// Walks the tables to find the physical address `virtual_address` maps to, huge pages included.
+    /// 
+    /// ## Returns
+    /// * 'Option<u64>' - The physical address, None if the address isn't mapped
+    pub fn translate(&self, virtual_address: u64) -> Option<u64> {
+        let indices = table_indices(virtual_address);
+        let mut table = self.table(self.pml4);
+        for level in 0..4 {
+            let entry = table.entries[indices[level]];
+            if !entry.is_present() {
+                return None;
+            }
+
+            // A huge page at the PDPT maps 1 GiB, at the PD it maps 2 MiB
+            let page_size: u64 = match level {
+                1 if entry.is_huge() => 1 << 30,
+                2 if entry.is_huge() => 1 << 21,
+                3 => FRAME_SIZE,
+                _ => {
+                    table = self.table(entry.address());
+                    continue;
+                }
+            };
+            return Some(entry.address() + virtual_address % page_size);
+        }
+        return None;
+    }
+
+    /// # Walk
+    /// 
+    /// Collects the PML4, PDPT, PD and PT entries used to translate `virtual_address`. The walk stops at the first
+    /// entry that isn't present or is a huge page, deeper levels are None.
+    pub fn walk(&self, virtual_address: u64) -> [Option<PageTableEntry>; 4] {
+        let mut entries = [None; 4];
+        let indices = table_indices(virtual_address);
+        let mut table = self.table(self.pml4);
+        for level in 0..4 {
+            let entry = table.entries[indices[level]];
+            entries[level] = Some(entry);
+            if !entry.is_present() || entry.is_huge() || level == 3 {
+                break;
+            }
+            table = self.table(entry.address());
+        }
+        return entries;
+    }
+
+    /// Returns the flags of the 4 KiB mapping of `virtual_address`, None if it isn't mapped.
+    pub fn flags(&self, virtual_address: u64) -> Option<PageFlags> {
+        let indices = table_indices(virtual_address);
+        let mut table = self.table(self.pml4);
+        for level in 0..3 {
+            let entry = table.entries[indices[level]];
+            if !entry.is_present() || entry.is_huge() {
+                return None;
+            }
+            table = self.table(entry.address());
+        }
+        let entry = table.entries[indices[3]];
+        if !entry.is_present() {
+            return None;
+        }
+        return Some(entry.flags());
+    }
+
+    // Walks the first `levels` levels for virtual_address, creating missing tables, and returns the last table reached.
+    fn create_tables(&mut self, virtual_address: u64, levels: usize, flags: PageFlags) -> Result<&mut PageTable, MapError> {
+        // Intermediate tables are as permissive as possible, the final entry decides the real permissions
+        let mut table_flags = PageFlags::PRESENT | PageFlags::WRITABLE;
+        if flags.contains(PageFlags::USER) {
+            table_flags |= PageFlags::USER;
+        }
+
+        let indices = table_indices(virtual_address);
+        let mut address = self.pml4;
+        for level in 0..levels {
+            let entry = &mut self.table_mut(address).entries[indices[level]];
+            if !entry.is_present() {
+                entry.set(alloc_table()?, table_flags);
+            } else if entry.is_huge() {
+                return Err(MapError::InsideHugePage);
+            } else {
+                entry.add_flags(table_flags);
+            }
+            address = entry.address();
+        }
+        return Ok(self.table_mut(address));
+    }
+
+    // Finds the present 4 KiB page table entry for virtual_address without creating tables.
+    fn leaf_entry(&mut self, virtual_address: u64) -> Option<&mut PageTableEntry> {
+        let indices = table_indices(virtual_address);
+        let mut address = self.pml4;
+        for level in 0..3 {
+            let entry = self.table(address).entries[indices[level]];
+            if !entry.is_present() || entry.is_huge() {
+                return None;
+            }
+            address = entry.address();
+        }
+        let entry = &mut self.table_mut(address).entries[indices[3]];
+        if !entry.is_present() {
+            return None;
+        }
+        return Some(entry);
+    }
+
+    // The table at a physical address taken from this manager's PML4 or one of its entries, read through the physmap.
+    fn table(&self, physical_address: u64) -> &PageTable {
+        // Safety: every table reachable from the PML4, or about to be, was allocated by alloc_table and stays mapped,
+        // and borrowing self keeps the tables from changing while the reference is in use
+        unsafe { &*table_ptr(physical_address) }
+    }
+
+    // Mutable version of table, borrowing self mutably means nothing else can be looking at the tables.
+    fn table_mut(&mut self, physical_address: u64) -> &mut PageTable {
+        // Safety: as for table
+        unsafe { &mut *table_ptr(physical_address) }
+    }
+}
+
+// Pointer to the page table at a physical address, through the physmap.
+fn table_ptr(physical_address: u64) -> *mut PageTable {
+    phys_to_virt(physical_address) as *mut PageTable
+}
+
+// Allocates a frame for a new table and zeroes it so every entry starts not present.
+fn alloc_table() -> Result<u64, MapError> {
+    let frame = FRAME_ALLOCATOR.lock().alloc_frame().ok_or(MapError::FrameAllocationFailed)?;
+    unsafe {
+        for entry in (*table_ptr(frame)).entries.iter_mut() {
+            *entry = PageTableEntry::empty();
+        }
+    }
+    return Ok(frame);
+}
@@ -0,0 +1,72 @@
+//! # Special virtual memory regions
+//! 
+//! Ranges of the address space that the page fault handler knows how to deal with.
+//! 
+//! [`VirtualRegion`]
+//! 
+//! [`RegionKind`]
+
+use super::PageFlags;
+use crate::sync::IrqSpinLock;
+
+const MAX_REGIONS: usize = 32;
+
+/// What a fault inside a region means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Backed on demand, the first access to each page maps a zeroed frame with these flags.
+    Demand(PageFlags),
+    /// Never mapped, any access is an overflow of whatever sits above it.
+    Guard,
+}
+
+/// # VirtualRegion
+/// 
+/// A named range of virtual addresses from `start` up to but not including `end`.
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualRegion {
+    pub start: u64,
+    pub end: u64,
+    pub kind: RegionKind,
+    pub name: &'static str,
+}
+
+impl VirtualRegion {
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.start && address < self.end
+    }
+}
+
+static REGIONS: IrqSpinLock<[Option<VirtualRegion>; MAX_REGIONS]> = IrqSpinLock::new([None; MAX_REGIONS]);
+
+/// Registers a region, returns false if the table is full or it overlaps an existing region.
+pub fn register_region(region: VirtualRegion) -> bool {
+    let mut regions = REGIONS.lock();
+    let overlaps = regions.iter().flatten().any(|r| region.start < r.end && r.start < region.end);
+    if overlaps {
+        return false;
+    }
+
+    match regions.iter_mut().find(|r| r.is_none()) {
+        Some(slot) => {
+            *slot = Some(region);
+            return true;
+        },
+        None => return false,
+    }
+}
+
+/// Removes the region starting at `start`.
+pub fn unregister_region(start: u64) -> () {
+    for slot in REGIONS.lock().iter_mut() {
+        if matches!(slot, Some(region) if region.start == start) {
+            *slot = None;
+        }
+    }
+}
+
+/// Finds the region containing `address`. Only tries the lock so it is safe to call from the page fault handler.
+pub fn find_region(address: u64) -> Option<VirtualRegion> {
+    let regions = REGIONS.try_lock()?;
+    return regions.iter().flatten().find(|region| region.contains(address)).copied();
+}
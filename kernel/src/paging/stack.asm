@@ -0,0 +1,12 @@
+[bits 64]
+
+; switch_stack(stack_top, entry, argument)
+; Moves onto a new stack and jumps to entry(argument), entry must never return
+switch_stack:
+   MOV RSP, RDI
+   XOR RBP, RBP
+   MOV RDI, RDX
+   PUSH 0            ; Fake return address, keeps the stack aligned as if entry was called
+   JMP RSI
+
+global switch_stack
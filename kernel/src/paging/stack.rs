@@ -0,0 +1,130 @@
+//! # Guarded kernel stacks
+//! 
+//! Every stack lives in its own slot of the kernel stack area, with the lowest page of the slot left unmapped. Running
+//! off the bottom of a stack hits that guard page instead of silently corrupting whatever is below it, and the fault
+//! handlers can tell exactly which stack overflowed.
+//! 
+//! [`KernelStack`]
+//! 
+//! [`alloc_stack`]
+
+use super::{FRAME_ALLOCATOR, FRAME_SIZE, PAGE_TABLE_MANAGER, PageFlags};
+use alloc::vec::Vec;
+use crate::sync::IrqSpinLock;
+
+/// Virtual address of the first stack slot.
+pub const KERNEL_STACKS_START: u64 = 0xFFFF_E000_0000_0000;
+// Each slot is 1 MiB, the guard page plus up to 255 pages of stack
+const SLOT_SIZE: u64 = 0x100000;
+/// Largest stack [`alloc_stack`] can hand out, in pages.
+pub const MAX_STACK_PAGES: u64 = SLOT_SIZE / FRAME_SIZE - 1;
+
+/// # KernelStack
+/// 
+/// A stack from [`alloc_stack`]. Stacks grow down, so `top` is what gets loaded into RSP or a TSS entry.
+#[derive(Debug, Clone, Copy)]
+pub struct KernelStack {
+    /// Lowest usable address, the guard page ends here.
+    pub bottom: u64,
+    /// One past the highest usable address.
+    pub top: u64,
+    pub name: &'static str,
+}
+
+struct StackSlots {
+    // Slot base address and name of the stacks in use
+    names: Vec<(u64, &'static str)>,
+    free: Vec<u64>,
+    next: u64,
+}
+
+static SLOTS: IrqSpinLock<StackSlots> = IrqSpinLock::new(StackSlots {
+    names: Vec::new(),
+    free: Vec::new(),
+    next: KERNEL_STACKS_START,
+});
+
+/// # Allocate stack
+/// 
+/// Maps a new stack of `pages` pages with an unmapped guard page below it. Needs the heap.
+/// 
+/// ## Arguments
+/// * 'pages' - size of the stack in pages, at most [`MAX_STACK_PAGES`]
+/// * 'name' - reported when the stack overflows
+/// 
+/// ## Returns
+/// * 'Option<KernelStack>' - The stack, None if pages is too large or memory ran out
+pub fn alloc_stack(pages: u64, name: &'static str) -> Option<KernelStack> {
+    if pages == 0 || pages > MAX_STACK_PAGES {
+        return None;
+    }
+
+    let mut slots = SLOTS.lock();
+    let slot = match slots.free.pop() {
+        Some(slot) => slot,
+        None => {
+            let slot = slots.next;
+            slots.next += SLOT_SIZE;
+            slot
+        }
+    };
+
+    let bottom = slot + FRAME_SIZE;
+    let flags = PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::NO_EXECUTE;
+    let mut manager = PAGE_TABLE_MANAGER.lock();
+    for i in 0..pages {
+        let page = bottom + i * FRAME_SIZE;
+        let mapped = match FRAME_ALLOCATOR.lock().alloc_frame() {
+            Some(frame) => manager.map(page, frame, flags).is_ok(),
+            None => false,
+        };
+        if !mapped {
+            // Give back whatever was mapped so far
+            for j in 0..i {
+                if let Some(frame) = manager.unmap(bottom + j * FRAME_SIZE) {
+                    FRAME_ALLOCATOR.lock().free_frame(frame);
+                }
+            }
+            drop(manager);
+            slots.free.push(slot);
+            return None;
+        }
+    }
+
+    // Pushing can grow the Vec, and a new heap page faults in through the page tables
+    drop(manager);
+    slots.names.push((slot, name));
+    return Some(KernelStack {
+        bottom,
+        top: bottom + pages * FRAME_SIZE,
+        name,
+    });
+}
+
+/// Unmaps a stack from [`alloc_stack`] and frees its frames, nothing may be running on it.
+pub fn free_stack(stack: KernelStack) -> () {
+    let slot = stack.bottom - FRAME_SIZE;
+    let mut slots = SLOTS.lock();
+    let mut manager = PAGE_TABLE_MANAGER.lock();
+    for page in (stack.bottom..stack.top).step_by(FRAME_SIZE as usize) {
+        if let Some(frame) = manager.unmap(page) {
+            FRAME_ALLOCATOR.lock().free_frame(frame);
+        }
+    }
+    drop(manager);
+    slots.names.retain(|&(base, _)| base != slot);
+    slots.free.push(slot);
+}
+
+/// # Guard page owner
+/// 
+/// Returns the name of the stack whose guard page contains `address`, None if it isn't in a guard page. Only tries
+/// the lock so it is safe to call from fault handlers.
+pub fn guard_page_owner(address: u64) -> Option<&'static str> {
+    if address < KERNEL_STACKS_START || (address - KERNEL_STACKS_START) % SLOT_SIZE >= FRAME_SIZE {
+        return None;
+    }
+    let slot = address - (address - KERNEL_STACKS_START) % SLOT_SIZE;
+    let slots = SLOTS.try_lock()?;
+    return slots.names.iter().find(|&&(base, _)| base == slot).map(|&(_, name)| name);
+}
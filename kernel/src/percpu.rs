@@ -0,0 +1,149 @@
+//! # Per-CPU data
+//!
+//! Every CPU gets its own copy of the `.percpu` linker section, found through the GS base. Variables declared with
+//! [`cpu_local!`] live in that section, so each CPU reads and writes its own copy without taking a lock.
+//!
+//! [`init`] sets up the current CPU's area
+//!
+//! [`CpuLocal`] a per-CPU variable
+//!
+//! [`cpu_id`]
+
+use crate::asm;
+use alloc::alloc::{alloc_zeroed, Layout};
+use core::ptr::addr_of;
+
+const IA32_GS_BASE: u32 = 0xC0000101;
+const IA32_KERNEL_GS_BASE: u32 = 0xC0000102;
+
+// Alignment of each CPU's area, variables aligned more than this would be misaligned in the copies
+const AREA_ALIGNMENT: usize = 64;
+
+extern "C" {
+    static __percpu_start: u8;
+    static __percpu_end: u8;
+}
+
+// Start of every CPU's area, the variables are copied in after it
+#[repr(C, align(64))]
+struct AreaHeader {
+    // Linear address of the area, GS relative loads can't see the GS base itself
+    self_pointer: u64,
+    cpu_id: u64,
+}
+
+/// # Declare a per-CPU variable
+///
+/// Declares a [`CpuLocal`] static in the `.percpu` section, every CPU starts with its own copy of the initial value.
+///
+/// ## Example
+///
+/// ```
+/// cpu_local! {
+///     pub static INTERRUPT_DEPTH: Cell<u32> = Cell::new(0);
+/// }
+///
+/// INTERRUPT_DEPTH.get().set(1);
+/// ```
+#[macro_export]
+macro_rules! cpu_local {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;) => {
+        $(#[$attr])*
+        #[link_section = ".percpu"]
+        $vis static $name: $crate::percpu::CpuLocal<$t> = $crate::percpu::CpuLocal::new($init);
+    };
+}
+
+/// # CpuLocal
+///
+/// A variable with a copy per CPU, declare them with [`cpu_local!`]. The static itself is only the template the copies
+/// are made from. Use [`core::cell::Cell`] or [`core::cell::RefCell`] for values that change, there is no locking since
+/// no other CPU can see the copy. Interrupt handlers run on the same CPU though, so a value also changed by one has to
+/// be accessed with interrupts off.
+pub struct CpuLocal<T> {
+    template: T,
+}
+
+// Every CPU only ever accesses its own copy, but values can still be moved out of one CPU's copy and used on another
+unsafe impl<T: Send> Sync for CpuLocal<T> {}
+
+impl<T> CpuLocal<T> {
+    pub const fn new(value: T) -> CpuLocal<T> {
+        CpuLocal { template: value }
+    }
+
+    /// The current CPU's copy. [`init`] must have run on this CPU, and the reference must not be kept once the thread
+    /// could be moved to another CPU.
+    pub fn get(&self) -> &T {
+        let offset = (self as *const CpuLocal<T>) as u64 - section_start();
+        unsafe { &(*((area_base() + header_size() + offset) as *const CpuLocal<T>)).template }
+    }
+}
+
+fn section_start() -> u64 {
+    addr_of!(__percpu_start) as u64
+}
+
+fn section_size() -> u64 {
+    addr_of!(__percpu_end) as u64 - addr_of!(__percpu_start) as u64
+}
+
+fn header_size() -> u64 {
+    core::mem::size_of::<AreaHeader>() as u64
+}
+
+// Address of the current CPU's area.
+fn area_base() -> u64 {
+    debug_assert_initialised();
+    let base: u64;
+    unsafe {
+        core::arch::asm!("mov {}, gs:[0]", out(reg) base, options(nostack, readonly, preserves_flags));
+    }
+    return base;
+}
+
+// Before init the GS base is 0, and reading gs:[0] would read whatever is at address 0
+fn debug_assert_initialised() -> () {
+    debug_assert!(asm::rdmsr(IA32_GS_BASE) != 0, "Per-CPU data used before percpu::init on this CPU");
+}
+
+/// # Init
+///
+/// Allocates the current CPU's area from the heap, copies the `.percpu` section into it and points the GS base at it.
+/// KERNEL_GS_BASE is set to the same area so a `swapgs` without a user mode GS can't lose it. Loading a GS selector
+/// clears the base, so nothing may do that afterwards.
+///
+/// ## Arguments
+/// * 'cpu_id' - index of the CPU, 0 for the BSP
+pub fn init(cpu_id: u64) -> () {
+    let size = header_size() + section_size();
+    let layout = Layout::from_size_align(size as usize, AREA_ALIGNMENT).expect("Bad per-CPU area layout");
+    let area = unsafe { alloc_zeroed(layout) };
+    if area.is_null() {
+        panic!("Failed to allocate the per-CPU area for CPU {}", cpu_id);
+    }
+
+    unsafe {
+        (area as *mut AreaHeader).write(AreaHeader {
+            self_pointer: area as u64,
+            cpu_id: cpu_id,
+        });
+        core::ptr::copy_nonoverlapping(
+            section_start() as *const u8,
+            area.add(header_size() as usize),
+            section_size() as usize,
+        );
+    }
+    asm::wrmsr(IA32_GS_BASE, area as u64);
+    asm::wrmsr(IA32_KERNEL_GS_BASE, area as u64);
+}
+
+/// Index of the current CPU, 0 for the BSP.
+pub fn cpu_id() -> u64 {
+    debug_assert_initialised();
+    let id: u64;
+    unsafe {
+        core::arch::asm!("mov {}, gs:[8]", out(reg) id, options(nostack, readonly, preserves_flags));
+    }
+    return id;
+}
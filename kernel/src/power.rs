@@ -0,0 +1,207 @@
+//! # Shutdown and reboot
+//! 
+//! Uses ACPI when the tables were found, then UEFI runtime services, with the legacy reset paths as a last resort.
+//! 
+//! [`shutdown`]
+//! 
+//! [`reboot`]
+
+use crate::acpi::{self, fadt::{Fadt, FLAG_RESET_REGISTER_SUPPORTED}, sdt::{checksum_valid, read_physical, SdtHeader, ADDRESS_SPACE_IO, ADDRESS_SPACE_MEMORY}};
+use crate::asm;
+use crate::efi::runtime::{self, ResetType};
+use crate::io::PS2;
+use crate::paging::{map_mmio, phys_to_virt};
+use crate::println;
+use core::mem::size_of;
+
+const PM1_SCI_ENABLE: u16 = 1 << 0;
+const PM1_SLEEP_TYPE_SHIFT: u16 = 10;
+const PM1_SLEEP_TYPE_MASK: u16 = 0x7 << PM1_SLEEP_TYPE_SHIFT;
+const PM1_SLEEP_ENABLE: u16 = 1 << 13;
+
+// AML opcodes needed to read the \_S5_ package out of the DSDT
+const AML_NAME_OP: u8 = 0x08;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_WORD_PREFIX: u8 = 0x0B;
+const AML_DWORD_PREFIX: u8 = 0x0C;
+const AML_QWORD_PREFIX: u8 = 0x0E;
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+
+/// # Shutdown
+/// 
+/// Enters the ACPI S5 soft off state, falling back to the firmware. Only returns after printing an error if the machine
+/// couldn't be turned off, callers should halt.
+pub fn shutdown() -> () {
+    asm::cli();
+    acpi_shutdown();
+    let error = runtime::reset_system(ResetType::Shutdown);
+    println!(0x00FF2222; "Firmware shutdown failed: {:?}", error);
+}
+
+// Writes SLP_TYPa/b with SLP_EN to the PM1 control blocks, returns if ACPI couldn't turn the machine off.
+fn acpi_shutdown() -> () {
+    let fadt = acpi::ACPI.lock().fadt;
+    let fadt = match fadt {
+        Some(fadt) if fadt.pm1a_control_block != 0 => fadt,
+        _ => {
+            println!(0x00FF2222; "ACPI shutdown needs the FADT");
+            return;
+        },
+    };
+    let (sleep_type_a, sleep_type_b) = match find_s5(fadt.dsdt) {
+        Some(types) => types,
+        None => {
+            println!(0x00FF2222; "ACPI shutdown failed, no \\_S5_ object in the DSDT");
+            return;
+        },
+    };
+
+    enable_acpi(&fadt);
+    enter_sleep_state(fadt.pm1a_control_block as u16, sleep_type_a);
+    if fadt.pm1b_control_block != 0 {
+        enter_sleep_state(fadt.pm1b_control_block as u16, sleep_type_b);
+    }
+
+    // Power off can take a moment to happen
+    for _ in 0..1000000 {
+        asm::nop();
+    }
+    println!(0x00FF2222; "ACPI shutdown failed");
+}
+
+// Sets SLP_TYP and SLP_EN in a PM1 control block. The other bits, SCI_EN among them, are kept as they are.
+fn enter_sleep_state(control_block: u16, sleep_type: u8) -> () {
+    let value = asm::inw(control_block) & !PM1_SLEEP_TYPE_MASK;
+    let sleep_type = (sleep_type as u16) << PM1_SLEEP_TYPE_SHIFT & PM1_SLEEP_TYPE_MASK;
+    asm::outw(control_block, value | sleep_type | PM1_SLEEP_ENABLE);
+}
+
+/// # Reboot
+/// 
+/// Tries the ACPI reset register, then the firmware, then the 8042 reset line, then triple faults.
+pub fn reboot() -> ! {
+    asm::cli();
+    if let Some(fadt) = acpi::ACPI.lock().fadt {
+        acpi_reset(&fadt);
+    }
+    runtime::reset_system(ResetType::Cold);
+
+    PS2.lock().reset_cpu();
+    for _ in 0..1000000 {
+        asm::nop();
+    }
+
+    triple_fault();
+}
+
+// Switches the chipset from legacy to ACPI mode, firmware usually does this already.
+fn enable_acpi(fadt: &Fadt) -> () {
+    if asm::inw(fadt.pm1a_control_block as u16) & PM1_SCI_ENABLE != 0 {
+        return;
+    }
+    if fadt.smi_command == 0 || fadt.acpi_enable == 0 {
+        return;
+    }
+    asm::outb(fadt.smi_command as u16, fadt.acpi_enable);
+    for _ in 0..1000000 {
+        if asm::inw(fadt.pm1a_control_block as u16) & PM1_SCI_ENABLE != 0 {
+            break;
+        }
+    }
+}
+
+// Writes the FADT reset value to the reset register, if the firmware says there is one.
+fn acpi_reset(fadt: &Fadt) -> () {
+    if fadt.flags & FLAG_RESET_REGISTER_SUPPORTED == 0 {
+        return;
+    }
+    let register = fadt.reset_register;
+    let address = register.address;
+    match register.address_space {
+        ADDRESS_SPACE_IO => asm::outb(address as u16, fadt.reset_value),
+        ADDRESS_SPACE_MEMORY => unsafe {
+            core::ptr::write_volatile(map_mmio(address, 1) as *mut u8, fadt.reset_value);
+        },
+        _ => {},
+    }
+}
+
+// Loads an empty IDT and raises an interrupt, the CPU can't deliver the resulting faults and resets.
+fn triple_fault() -> ! {
+    let descriptor: [u16; 5] = [0; 5];
+    unsafe {
+        core::arch::asm!("lidt [{}]", "int3", in(reg) descriptor.as_ptr());
+    }
+    loop {
+        asm::hlt();
+    }
+}
+
+/// # Find \_S5_
+/// 
+/// Scans the DSDT's AML for the `Name(\_S5_, Package() {...})` object without a full interpreter.
+/// 
+/// ## Returns
+/// * 'Option<(u8, u8)>' - SLP_TYPa and SLP_TYPb for the soft off state
+fn find_s5(dsdt: u64) -> Option<(u8, u8)> {
+    if dsdt == 0 {
+        return None;
+    }
+    let header: SdtHeader = read_physical(dsdt);
+    if &header.signature != b"DSDT" || (header.length as usize) < size_of::<SdtHeader>() {
+        return None;
+    }
+    if !checksum_valid(dsdt, header.length as usize) {
+        return None;
+    }
+    let aml = unsafe {
+        core::slice::from_raw_parts(
+            phys_to_virt(dsdt + size_of::<SdtHeader>() as u64) as *const u8,
+            header.length as usize - size_of::<SdtHeader>(),
+        )
+    };
+
+    let position = aml.windows(4).position(|window| window == b"_S5_")?;
+    // The name is either NameOp _S5_ or NameOp \_S5_
+    let is_name = (position >= 1 && aml[position - 1] == AML_NAME_OP)
+        || (position >= 2 && aml[position - 1] == b'\\' && aml[position - 2] == AML_NAME_OP);
+    if !is_name || aml.get(position + 4) != Some(&AML_PACKAGE_OP) {
+        return None;
+    }
+
+    // Skip PackageOp, the PkgLength whose top two bits count its extra bytes, and NumElements
+    let mut index = position + 5;
+    let length_bytes = (*aml.get(index)? >> 6) as usize;
+    index += 1 + length_bytes + 1;
+
+    let sleep_type_a = read_aml_integer(aml, &mut index)?;
+    let sleep_type_b = read_aml_integer(aml, &mut index)?;
+    return Some((sleep_type_a, sleep_type_b));
+}
+
+// Reads an AML integer constant and moves past it, None if it isn't one or doesn't fit a sleep type.
+fn read_aml_integer(aml: &[u8], index: &mut usize) -> Option<u8> {
+    let size = match *aml.get(*index)? {
+        AML_ZERO_OP => {
+            *index += 1;
+            return Some(0);
+        },
+        AML_ONE_OP => {
+            *index += 1;
+            return Some(1);
+        },
+        AML_BYTE_PREFIX => 1,
+        AML_WORD_PREFIX => 2,
+        AML_DWORD_PREFIX => 4,
+        AML_QWORD_PREFIX => 8,
+        _ => return None,
+    };
+
+    // The prefix is followed by the value in little endian
+    let bytes = aml.get(*index + 1..*index + 1 + size)?;
+    let value = bytes.iter().rev().fold(0u64, |value, &byte| value << 8 | byte as u64);
+    *index += 1 + size;
+    return u8::try_from(value).ok();
+}
@@ -4,44 +4,60 @@
 
 use crate::efi::Framebuffer;
 use crate::math::minimum;
+use crate::paging::phys_to_virt;
 
-//abstract framebuffer to this file
-static mut FB_PTR: *const Framebuffer = core::ptr::null();
+//abstract framebuffer to this file, copied out of the bootloader's memory so it survives reclaiming it
+static mut FRAMEBUFFER: Framebuffer = Framebuffer {
+    base_address: core::ptr::null_mut(),
+    buffer_size: 0,
+    width: 0,
+    height: 0,
+    pixels_per_scan_line: 0,
+};
 
 pub unsafe fn gop_init(fb_ptr: *const Framebuffer) -> () {
-    FB_PTR = fb_ptr;
+    FRAMEBUFFER = *fb_ptr;
+}
+
+/// A copy of the framebuffer info, valid after [`gop_init`].
+pub fn framebuffer() -> Framebuffer {
+    unsafe { FRAMEBUFFER }
+}
+
+//the framebuffer is accessed through the physmap once the kernel owns the page tables
+#[inline(always)]
+unsafe fn base_address() -> *mut u32 {
+    phys_to_virt(FRAMEBUFFER.base_address as u64) as *mut u32
 }
 
 //unsafe can write past framebuffer if x and y are too large
 #[inline(always)]
 pub unsafe fn plot_pixel(x: u32, y: u32, rgb: u32) -> () {
-    *((*FB_PTR)
-        .base_address
-        .offset(((*FB_PTR).pixels_per_scan_line * y + x) as isize)) = rgb;
+    *(base_address().offset((FRAMEBUFFER.pixels_per_scan_line * y + x) as isize)) = rgb;
 }
 
 pub fn plot_rect(x: u32, y: u32, width: u32, height: u32, hex: u32) -> () {
     unsafe {
-        if x > (*FB_PTR).pixels_per_scan_line || y > (*FB_PTR).height {
+        if x > FRAMEBUFFER.pixels_per_scan_line || y > FRAMEBUFFER.height {
             return;
         }
 
-        let mut offset = (*FB_PTR).pixels_per_scan_line * y + x;
-        let actual_height = minimum(height, (*FB_PTR).height - y);
-        let actual_width = minimum(width, (*FB_PTR).pixels_per_scan_line - x);
+        let mut offset = FRAMEBUFFER.pixels_per_scan_line * y + x;
+        let actual_height = minimum(height, FRAMEBUFFER.height - y);
+        let actual_width = minimum(width, FRAMEBUFFER.pixels_per_scan_line - x);
 
         for _ in y..y + actual_height {
             for _ in x..x + actual_width {
-                *((*FB_PTR).base_address.offset(offset as isize)) = hex;
+                *(base_address().offset(offset as isize)) = hex;
                 offset += 1;
             }
-            offset += (*FB_PTR).width - actual_width;
+            offset += FRAMEBUFFER.width - actual_width;
         }
     }
 }
 
 pub fn clear_screen() -> () {
     unsafe {
-        plot_rect(0, 0, (*FB_PTR).pixels_per_scan_line, (*FB_PTR).height, 0u32);
+        plot_rect(0, 0, FRAMEBUFFER.pixels_per_scan_line, FRAMEBUFFER.height, 0u32);
     }
 }
@@ -1,4 +1,4 @@
-//! # JankOS printing module for low level safe printing using GOP
+//! # JankOS printing module for low level safe printing using GOP, mirrored to the serial port
 //! 
 //! [`Writer`]
 //! 
@@ -9,9 +9,12 @@
 mod gop;
 
 use crate::efi::Framebuffer;
+use crate::io::serial::COM1;
 use gop::{plot_pixel, clear_screen, gop_init};
+pub use gop::framebuffer;
 use core::fmt::{self, Write};
-use spin::Mutex;
+use kernel_lib::text::TextGrid;
+use crate::sync::IrqSpinLock;
 
 /// # Writer singleton
 /// A singleton that acts as the backend to the safe handling of the print! and println! macros
@@ -27,25 +30,21 @@ use spin::Mutex;
 /// "Address: 0x1000" 
 pub struct Writer {
     cursor: u32,
-    max_cursor: u32,
-    line_length: u32,
-    lines_count: u32,
+    grid: TextGrid,
     colour: u32,
-    columns: bool,
 }
 
-// TODO: This raw pointer to the start of the glyph buffer is annoying, we should not keep a mutable raw pointer 
-// floating around but safely incorperating it as a mutex or lazy static is not possible with raw pointers
-static mut GB_PTR: *const u8 = core::ptr::null_mut();
+// 256 psf1 glyphs, 8x16 pixels so 16 bytes each
+const GLYPH_BUFFER_SIZE: usize = 256 * 16;
 
-// Global, thread-safe writer instance.
-static WRITER: Mutex<Writer> = Mutex::new(Writer {
+// The font is copied out of the bootloader's memory so it survives reclaiming it
+static mut GLYPH_BUFFER: [u8; GLYPH_BUFFER_SIZE] = [0; GLYPH_BUFFER_SIZE];
+
+// Global writer instance, interrupt handlers print too so it is held with interrupts off
+static WRITER: IrqSpinLock<Writer> = IrqSpinLock::new(Writer {
     cursor: 0,
-    max_cursor: 0,
-    line_length: 98,
-    lines_count: 37,
+    grid: TextGrid::new(98, 37, false),
     colour: 0x00FFFFFF,
-    columns: false,
 });
 
 /// # Print
@@ -143,14 +142,20 @@ macro_rules! println {
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments ){
     WRITER.lock().write_fmt(args).unwrap();
+    COM1.lock().write_fmt(args).unwrap();
 }
 
 #[doc(hidden)]
 pub fn _print_colour(c: u32, args: fmt::Arguments){
-    let prev_colour = WRITER.lock().colour;
-    WRITER.lock().colour = c;
-    WRITER.lock().write_fmt(args).unwrap();
-    WRITER.lock().colour = prev_colour;
+    {
+        // Locked once so nothing else prints in the colour in between
+        let mut writer = WRITER.lock();
+        let prev_colour = writer.colour;
+        writer.colour = c;
+        writer.write_fmt(args).unwrap();
+        writer.colour = prev_colour;
+    }
+    COM1.lock().write_fmt(args).unwrap();
 }
 
 impl fmt::Write for Writer {
@@ -187,22 +192,9 @@ impl Writer {
             gop_init(fb_ptr);
             clear_screen();
 
-            GB_PTR = gb_ptr;
+            core::ptr::copy_nonoverlapping(gb_ptr, core::ptr::addr_of_mut!(GLYPH_BUFFER) as *mut u8, GLYPH_BUFFER_SIZE);
 
-            let mut len = (*fb_ptr).pixels_per_scan_line / 8 - 2;
-            let lin = (*fb_ptr).height / 16;
-            let max = if columns {
-                len = len / 2 - 1;
-                (len * 2) * lin
-            } else {
-                len * lin
-            };
-
-            let mut writer = WRITER.lock();
-            writer.max_cursor = max;
-            writer.line_length = len;
-            writer.lines_count = lin;
-            writer.columns = columns;
+            WRITER.lock().grid = TextGrid::from_resolution((*fb_ptr).pixels_per_scan_line, (*fb_ptr).height, columns);
         }
     }
 
@@ -212,18 +204,9 @@ impl Writer {
 
     // Prints a character aligned with the character buffer grid
     unsafe fn place_char(&mut self, c: u8) {
-        let loc = self.cursor;
-        let x;
-        let y;
-        if self.columns && (loc >= (self.line_length * self.lines_count)) {
-            x = (loc % self.line_length) * 8 + ((self.line_length + 1) * 8);
-            y = ((loc / self.line_length) * 16) - (self.lines_count * 16);
-        } else {
-            x = (loc % self.line_length) * 8 + 8;
-            y = (loc / self.line_length) * 16;
-        }
+        let (x, y) = self.grid.char_position(self.cursor);
 
-        let mut font_ptr: *const u8 = GB_PTR.offset(((c as u32) * 16) as isize);
+        let mut font_ptr: *const u8 = (core::ptr::addr_of!(GLYPH_BUFFER) as *const u8).offset(((c as u32) * 16) as isize);
         for i in y..y + 16 {
             for j in x..x + 8 {
                 if (*font_ptr & 0b10000000 >> (j - x)) > 0 {
@@ -263,13 +246,13 @@ impl Writer {
 
     // Moves cursor to next line
     unsafe fn newline(&mut self) -> () {
-        let number = self.line_length - (self.cursor % self.line_length);
+        let number = self.grid.newline_advance(self.cursor);
         self.inc_cursor(number);
     }
 
     // Moves cursor to nearest denomination of 4
     unsafe fn tab(&mut self) -> () {
-        let number = 4 - (self.line_length - (self.cursor % self.line_length)) % 4;
+        let number = self.grid.tab_advance(self.cursor);
         self.inc_cursor(number);
     }
 
@@ -0,0 +1,180 @@
+//! # Symmetric multiprocessing
+//!
+//! Starts the application processors listed in the ACPI MADT with the INIT-SIPI-SIPI sequence. Each one runs the real
+//! mode trampoline in `trampoline.asm`, then loads its own GDT and TSS and the shared IDT before idling.
+//!
+//! [`init`]
+//!
+//! [`cpu_count`]
+
+use crate::{acpi, asm, gdt, interrupts, percpu, println};
+use crate::io::{apic, pit};
+use crate::paging::{phys_to_virt, stack, MapError, PageFlags, FRAME_SIZE, PAGE_TABLE_MANAGER};
+use alloc::vec::Vec;
+use core::ptr::addr_of;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Physical address the trampoline is copied to, APs start executing here.
+pub const TRAMPOLINE_BASE: u64 = 0x8000;
+// Low memory copy of the kernel's PML4 the trampoline enables paging with
+const TRAMPOLINE_PML4: u64 = 0x9000;
+
+const AP_STACK_PAGES: u64 = 16;
+
+// Delays from Intel's multiprocessor specification
+const INIT_DELAY_US: u32 = 10_000;
+const STARTUP_DELAY_US: u32 = 200;
+// How long an AP has to reach ap_main before it is given up on
+const START_TIMEOUT_MS: u32 = 1000;
+
+extern "C" {
+    static trampoline_start: u8;
+    static trampoline_data: u8;
+    static trampoline_end: u8;
+}
+
+// The data block at the end of the trampoline
+#[repr(C)]
+struct TrampolineData {
+    pml4: u64,
+    efer: u64,
+    cr3: u64,
+    stack: u64,
+    entry: u64,
+    argument: u64,
+}
+
+// CPUs that have reached their idle loop, including the BSP
+static CPU_COUNT: AtomicUsize = AtomicUsize::new(1);
+// Set by the AP being started once it no longer needs the trampoline
+static AP_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Number of CPUs running, 1 until [`init`] has started the others.
+pub fn cpu_count() -> usize {
+    CPU_COUNT.load(Ordering::Relaxed)
+}
+
+/// # Init
+///
+/// Starts every usable CPU in the MADT apart from the current one, one at a time since they share the trampoline.
+/// Needs the APIC and the PIT, CPUs that don't respond within a second are skipped.
+pub fn init() -> () {
+    if !apic::is_enabled() {
+        println!(0x00FF2222; "SMP needs the APIC, only the BSP is used");
+        return;
+    }
+    let processors: Vec<u32> = match &acpi::ACPI.lock().madt {
+        Some(madt) => madt.processors.iter().filter(|p| p.usable).map(|p| p.apic_id).collect(),
+        None => {
+            println!(0x00FF2222; "SMP needs the ACPI MADT, only the BSP is used");
+            return;
+        },
+    };
+    let bsp = apic::LOCAL_APIC.lock().id() as u32;
+
+    install_trampoline();
+    for &apic_id in processors.iter().filter(|&&id| id != bsp) {
+        // Startup IPIs can only be sent to 8 bit xAPIC IDs
+        if apic_id > 0xFF || !start_ap(apic_id as u8, cpu_count() as u64) {
+            println!(0x00FF2222; "Failed to start the CPU with APIC ID {}", apic_id);
+        }
+    }
+    println!(0x0022FF22; "-- Started {} of {} CPUs", cpu_count(), processors.len());
+}
+
+// Copies the trampoline and a low memory PML4 to their fixed addresses, below 1 MiB where the frame allocator never
+// hands out memory.
+fn install_trampoline() -> () {
+    let start = addr_of!(trampoline_start) as u64;
+    let end = addr_of!(trampoline_end) as u64;
+    assert!(end - start <= TRAMPOLINE_PML4 - TRAMPOLINE_BASE, "The AP trampoline doesn't fit below its PML4");
+
+    {
+        // The trampoline runs from its physical address until it jumps to ap_main
+        let mut manager = PAGE_TABLE_MANAGER.lock();
+        for page in [TRAMPOLINE_BASE, TRAMPOLINE_PML4] {
+            match manager.map(page, page, PageFlags::PRESENT | PageFlags::WRITABLE) {
+                Ok(()) | Err(MapError::AlreadyMapped) => {},
+                Err(e) => panic!("Failed to map the AP trampoline: {:?}", e),
+            }
+        }
+    }
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(start as *const u8, phys_to_virt(TRAMPOLINE_BASE) as *mut u8, (end - start) as usize);
+        let kernel_pml4 = asm::read_cr3() & !(FRAME_SIZE - 1);
+        core::ptr::copy_nonoverlapping(
+            phys_to_virt(kernel_pml4) as *const u8,
+            phys_to_virt(TRAMPOLINE_PML4) as *mut u8,
+            FRAME_SIZE as usize,
+        );
+    }
+}
+
+/// # Start AP
+///
+/// Points the trampoline at a fresh stack and sends the CPU an INIT and up to two startup IPIs.
+///
+/// ## Arguments
+/// * 'apic_id' - local APIC ID of the CPU
+/// * 'cpu' - index the kernel gives the CPU, passed to [`ap_main`]
+///
+/// ## Returns
+/// * 'bool' - true once the CPU is running kernel code
+fn start_ap(apic_id: u8, cpu: u64) -> bool {
+    let stack = match stack::alloc_stack(AP_STACK_PAGES, "application processor") {
+        Some(stack) => stack,
+        None => return false,
+    };
+
+    let data_offset = addr_of!(trampoline_data) as u64 - addr_of!(trampoline_start) as u64;
+    let data = phys_to_virt(TRAMPOLINE_BASE + data_offset) as *mut TrampolineData;
+    unsafe {
+        data.write_volatile(TrampolineData {
+            pml4: TRAMPOLINE_PML4,
+            efer: asm::read_efer(),
+            cr3: asm::read_cr3(),
+            stack: stack.top,
+            entry: ap_main as u64,
+            argument: cpu,
+        });
+    }
+    AP_STARTED.store(false, Ordering::SeqCst);
+
+    let page = (TRAMPOLINE_BASE / FRAME_SIZE) as u8;
+    apic::LOCAL_APIC.lock().send_init(apic_id);
+    pit::busy_wait_us(INIT_DELAY_US);
+    for _ in 0..2 {
+        apic::LOCAL_APIC.lock().send_startup(apic_id, page);
+        pit::busy_wait_us(STARTUP_DELAY_US);
+        if AP_STARTED.load(Ordering::SeqCst) {
+            return true;
+        }
+    }
+
+    for _ in 0..START_TIMEOUT_MS {
+        if AP_STARTED.load(Ordering::SeqCst) {
+            return true;
+        }
+        pit::busy_wait_us(1000);
+    }
+    // The stack is leaked, the CPU might still wake up and use it
+    return false;
+}
+
+// Where the trampoline leaves an AP, in long mode on the kernel's page tables and its own stack.
+extern "C" fn ap_main(cpu: u64) -> ! {
+    percpu::init(cpu);
+    gdt::init_gdt();
+    interrupts::load_idt();
+    apic::init_ap();
+
+    CPU_COUNT.fetch_add(1, Ordering::SeqCst);
+    AP_STARTED.store(true, Ordering::SeqCst);
+    println!(0x0022FF22; "-- CPU {} online", cpu);
+
+    asm::sti();
+    loop {
+        asm::hlt();
+    }
+}
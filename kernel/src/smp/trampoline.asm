@@ -0,0 +1,97 @@
+; Real mode entry point for the application processors, smp::init copies it to TRAMPOLINE_BASE. A startup IPI starts
+; the AP there in real mode, this takes it through protected mode into long mode on the kernel's page tables and jumps
+; to the entry point in the data block at the end.
+
+TRAMPOLINE_BASE equ 0x8000
+
+; Address of a label once the trampoline has been copied to TRAMPOLINE_BASE
+%define ADDRESS(label) (TRAMPOLINE_BASE + (label - trampoline_start))
+
+section .rodata
+
+[bits 16]
+trampoline_start:
+   CLI
+   CLD
+   XOR AX, AX
+   MOV DS, AX
+   O32 LGDT [ADDRESS(trampoline_gdt_pointer)]
+   MOV EAX, CR0
+   OR EAX, 1                        ; Protected mode
+   MOV CR0, EAX
+   JMP DWORD 0x08:ADDRESS(trampoline_protected)
+
+[bits 32]
+trampoline_protected:
+   MOV AX, 0x18
+   MOV DS, AX
+   MOV ES, AX
+   MOV SS, AX
+
+   MOV EAX, CR4
+   OR EAX, (1 << 5) | (1 << 7)      ; PAE and global pages
+   MOV CR4, EAX
+   ; CR3 only holds 32 bits here, so this is a copy of the kernel's PML4 in low memory
+   MOV EAX, [ADDRESS(trampoline_pml4)]
+   MOV CR3, EAX
+
+   ; The BSP's EFER, which has no-execute enabled if the kernel uses it, plus long mode
+   MOV ECX, 0xC0000080
+   MOV EAX, [ADDRESS(trampoline_efer)]
+   MOV EDX, [ADDRESS(trampoline_efer) + 4]
+   OR EAX, 1 << 8
+   WRMSR
+
+   MOV EAX, CR0
+   OR EAX, (1 << 31) | (1 << 16)    ; Paging and write protect
+   MOV CR0, EAX
+   JMP 0x10:ADDRESS(trampoline_long)
+
+[bits 64]
+trampoline_long:
+   MOV AX, 0x18
+   MOV DS, AX
+   MOV ES, AX
+   MOV SS, AX
+   XOR AX, AX
+   MOV FS, AX
+   MOV GS, AX
+
+   ; The kernel's own PML4, the trampoline is identity mapped in it too
+   MOV RAX, [ADDRESS(trampoline_cr3)]
+   MOV CR3, RAX
+   MOV RSP, [ADDRESS(trampoline_stack)]
+   XOR RBP, RBP
+   MOV RDI, [ADDRESS(trampoline_argument)]
+   MOV RAX, [ADDRESS(trampoline_entry)]
+   PUSH 0                           ; Fake return address, keeps the stack aligned as if entry was called
+   JMP RAX
+
+align 8
+trampoline_gdt:
+   DQ 0
+   DQ 0x00CF9A000000FFFF            ; 0x08 32 bit code
+   DQ 0x00AF9A000000FFFF            ; 0x10 64 bit code
+   DQ 0x00CF92000000FFFF            ; 0x18 data
+trampoline_gdt_pointer:
+   DW trampoline_gdt_pointer - trampoline_gdt - 1
+   DD ADDRESS(trampoline_gdt)
+
+; Filled in by smp::start_ap before each startup IPI, the layout must match smp::TrampolineData
+align 8
+trampoline_data:
+trampoline_pml4:
+   DQ 0
+trampoline_efer:
+   DQ 0
+trampoline_cr3:
+   DQ 0
+trampoline_stack:
+   DQ 0
+trampoline_entry:
+   DQ 0
+trampoline_argument:
+   DQ 0
+trampoline_end:
+
+global trampoline_start, trampoline_data, trampoline_end
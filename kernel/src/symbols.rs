@@ -0,0 +1,57 @@
+//! # Kernel symbols
+//!
+//! The kernel's ELF symbol table, copied out of the bootloader's memory so faults can name the function they happened
+//! in.
+//!
+//! [`init`] / [`lookup`]
+
+use crate::efi::BootInfo;
+use crate::paging::phys_to_virt;
+use crate::println;
+use alloc::vec::Vec;
+use kernel_lib::symbols::{self, ElfSymbol};
+use spin::Once;
+
+struct SymbolTable {
+    symbols: Vec<ElfSymbol>,
+    strings: Vec<u8>,
+}
+
+static SYMBOLS: Once<SymbolTable> = Once::new();
+
+/// # Init
+///
+/// Copies the symbol and string tables the bootloader loaded onto the heap, must be called before
+/// [`crate::paging::reclaim_boot_memory`] frees them.
+pub fn init(boot_info: &BootInfo) -> () {
+    if boot_info.symbol_table == 0 || boot_info.string_table == 0 {
+        println!(0x00FF2222; "-- No kernel symbols, faults are reported by address only");
+        return;
+    }
+
+    let count = boot_info.symbol_table_size as usize / core::mem::size_of::<ElfSymbol>();
+    let (symbols, strings) = unsafe {
+        (
+            core::slice::from_raw_parts(phys_to_virt(boot_info.symbol_table) as *const ElfSymbol, count).to_vec(),
+            core::slice::from_raw_parts(phys_to_virt(boot_info.string_table) as *const u8,
+                boot_info.string_table_size as usize).to_vec(),
+        )
+    };
+    SYMBOLS.call_once(|| SymbolTable {
+        symbols: symbols,
+        strings: strings,
+    });
+    println!(0x0022FF22; "-- Loaded {} kernel symbols", count);
+}
+
+/// # Lookup
+///
+/// Finds the function containing `address`.
+///
+/// ## Returns
+/// * 'Option<(&str, u64)>' - The function's mangled name, print it with [`kernel_lib::symbols::Demangle`], and the
+///   offset into it. None before [`init`] or if no function covers the address
+pub fn lookup(address: u64) -> Option<(&'static str, u64)> {
+    let table = SYMBOLS.get()?;
+    return symbols::find_symbol(&table.symbols, &table.strings, address);
+}
@@ -0,0 +1,57 @@
+//! # Condition variable
+//!
+//! [`Condvar`]
+
+use super::{MutexGuard, WaitQueue};
+
+/// # Condvar
+///
+/// Lets threads holding a [`super::Mutex`] sleep until another thread changes the data it protects.
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Condvar {
+        Condvar { waiters: WaitQueue::new() }
+    }
+
+    /// # Wait
+    ///
+    /// Releases the mutex and blocks until notified, then locks the mutex again. Giving up the mutex and going to sleep
+    /// happen together, so a notify from the next holder can't be missed. Wake ups can also come from a notify meant for
+    /// an earlier state, so check the condition again or use [`Condvar::wait_while`].
+    ///
+    /// ## Arguments
+    /// * 'guard' - the held mutex
+    ///
+    /// ## Returns
+    /// * 'MutexGuard<T>' - the same mutex, locked again
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        self.waiters.wait_releasing(|| drop(guard));
+        return mutex.lock();
+    }
+
+    /// Waits for as long as `condition` returns true for the protected data.
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        return guard;
+    }
+
+    /// Wakes one waiting thread, returns false if none was waiting.
+    pub fn notify_one(&self) -> bool {
+        self.waiters.notify_one()
+    }
+
+    /// Wakes every waiting thread, returns how many there were.
+    pub fn notify_all(&self) -> usize {
+        self.waiters.notify_all()
+    }
+}
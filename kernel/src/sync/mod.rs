@@ -0,0 +1,29 @@
+//! # Synchronisation
+//!
+//! Locks that work with interrupts and the scheduler. The blocking primitives check their condition and go to sleep
+//! with interrupts off, which only rules out a lost wake up on one CPU, so they rely on threads only running on the CPU
+//! that started the scheduler.
+//!
+//! [`IrqSpinLock`] a spinlock that keeps interrupts off while held, for data interrupt handlers use
+//!
+//! [`WaitQueue`] threads waiting to be notified
+//!
+//! [`Mutex`] / [`Semaphore`] / [`Condvar`] / [`RwLock`] sleep while they wait
+
+pub mod condvar;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod spinlock;
+pub mod wait_queue;
+
+// Not every primitive has a user in the kernel yet
+#[allow(unused_imports)]
+pub use self::{
+    condvar::Condvar,
+    mutex::{Mutex, MutexGuard},
+    rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    semaphore::Semaphore,
+    spinlock::{IrqSpinLock, IrqSpinLockGuard},
+    wait_queue::WaitQueue,
+};
@@ -0,0 +1,126 @@
+//! # Reader-writer lock
+//!
+//! [`RwLock`] / [`RwLockReadGuard`] / [`RwLockWriteGuard`]
+
+use super::WaitQueue;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// State of a lock held for writing, otherwise the state is the number of readers
+const WRITER: usize = usize::MAX;
+
+/// # RwLock
+///
+/// Any number of readers or one writer, threads that have to wait for it sleep. Readers can keep taking the lock while a
+/// writer waits, so it suits data that is written rarely.
+pub struct RwLock<T> {
+    state: AtomicUsize,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+/// Shared access to an [`RwLock`].
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+/// Exclusive access to an [`RwLock`].
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> RwLock<T> {
+        RwLock {
+            state: AtomicUsize::new(0),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Blocks while a writer holds the lock.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        if !self.acquire_read() {
+            self.waiters.wait_until(|| self.acquire_read());
+        }
+        return RwLockReadGuard { lock: self };
+    }
+
+    /// Blocks until nothing else holds the lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        if !self.acquire_write() {
+            self.waiters.wait_until(|| self.acquire_write());
+        }
+        return RwLockWriteGuard { lock: self };
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        match self.acquire_read() {
+            true => Some(RwLockReadGuard { lock: self }),
+            false => None,
+        }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        match self.acquire_write() {
+            true => Some(RwLockWriteGuard { lock: self }),
+            false => None,
+        }
+    }
+
+    fn acquire_read(&self) -> bool {
+        self.state
+            // Stops a reader short of WRITER, so a full count can't look like a writer
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |readers| match readers < WRITER - 1 {
+                true => Some(readers + 1),
+                false => None,
+            })
+            .is_ok()
+    }
+
+    fn acquire_write(&self) -> bool {
+        self.state.compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        // The last reader out lets a writer in
+        if self.lock.state.fetch_sub(1, Ordering::Release) == 1 {
+            self.lock.waiters.notify_all();
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+        self.lock.waiters.notify_all();
+    }
+}
@@ -0,0 +1,48 @@
+//! # Counting semaphore
+//!
+//! [`Semaphore`]
+
+use super::WaitQueue;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// # Semaphore
+///
+/// A count of permits, taking one blocks while there are none. [`Semaphore::release`] is safe to call from interrupt
+/// handlers, so a handler can use one to hand work to a thread.
+pub struct Semaphore {
+    permits: AtomicUsize,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Takes a permit, blocking until there is one.
+    pub fn acquire(&self) -> () {
+        if !self.try_acquire() {
+            self.waiters.wait_until(|| self.try_acquire());
+        }
+    }
+
+    /// Takes a permit without blocking, returns false if there were none.
+    pub fn try_acquire(&self) -> bool {
+        self.permits
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |permits| permits.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Gives a permit back and wakes a waiting thread.
+    pub fn release(&self) -> () {
+        self.permits.fetch_add(1, Ordering::Release);
+        self.waiters.notify_one();
+    }
+
+    pub fn permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
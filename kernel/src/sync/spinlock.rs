@@ -0,0 +1,86 @@
+//! # Interrupt safe spinlock
+//!
+//! [`IrqSpinLock`] / [`IrqSpinLockGuard`]
+
+use crate::asm;
+use crate::interrupts::interrupts_enabled;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+/// # IrqSpinLock
+///
+/// A spinlock that turns interrupts off on the current CPU while it is held, so an interrupt handler taking the same
+/// lock can never spin on the code it interrupted. Use it for anything an interrupt handler locks.
+pub struct IrqSpinLock<T> {
+    inner: spin::Mutex<T>,
+}
+
+/// Holds an [`IrqSpinLock`], interrupts are turned back on when it is dropped if they were on when it was locked.
+pub struct IrqSpinLockGuard<'a, T> {
+    // Dropped by hand so the lock is released before interrupts come back on
+    guard: ManuallyDrop<spin::MutexGuard<'a, T>>,
+    enabled: bool,
+}
+
+impl<T> IrqSpinLock<T> {
+    pub const fn new(value: T) -> IrqSpinLock<T> {
+        IrqSpinLock { inner: spin::Mutex::new(value) }
+    }
+
+    /// Turns interrupts off, then spins until the lock is free.
+    pub fn lock(&self) -> IrqSpinLockGuard<'_, T> {
+        let enabled = interrupts_enabled();
+        asm::cli();
+        return IrqSpinLockGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            enabled: enabled,
+        };
+    }
+
+    /// Locks without spinning, None if the lock is held.
+    pub fn try_lock(&self) -> Option<IrqSpinLockGuard<'_, T>> {
+        let enabled = interrupts_enabled();
+        asm::cli();
+        match self.inner.try_lock() {
+            Some(guard) => Some(IrqSpinLockGuard {
+                guard: ManuallyDrop::new(guard),
+                enabled: enabled,
+            }),
+            None => {
+                if enabled {
+                    asm::sti();
+                }
+                None
+            },
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+}
+
+impl<'a, T> Deref for IrqSpinLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for IrqSpinLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<'a, T> Drop for IrqSpinLockGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.guard);
+        }
+        if self.enabled {
+            asm::sti();
+        }
+    }
+}
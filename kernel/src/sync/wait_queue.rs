@@ -0,0 +1,97 @@
+//! # Wait queue
+//!
+//! [`WaitQueue`] threads blocked until something notifies them
+
+use super::IrqSpinLock;
+use crate::interrupts::{in_interrupt, without_interrupts};
+use crate::task::{self, ThreadQueue, MAX_THREADS};
+
+/// # WaitQueue
+///
+/// Threads blocked until another thread or an interrupt handler notifies them, woken in the order they started waiting.
+/// The other primitives in [`super`] are built on it.
+pub struct WaitQueue {
+    // Threads join it with interrupts off, so it can't be anything that allocates
+    waiters: IrqSpinLock<ThreadQueue>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> WaitQueue {
+        WaitQueue { waiters: IrqSpinLock::new(ThreadQueue::new()) }
+    }
+
+    /// Blocks the current thread until it is notified.
+    pub fn wait(&self) -> () {
+        self.wait_releasing(|| ());
+    }
+
+    /// # Wait until
+    ///
+    /// Blocks the current thread until `condition` returns true. The condition is checked with interrupts off, so
+    /// nothing on this CPU can notify between a false check and the thread going to sleep.
+    ///
+    /// ## Arguments
+    /// * 'condition' - checked before blocking and again after every wake up
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) -> () {
+        loop {
+            let done = without_interrupts(|| {
+                if condition() {
+                    return true;
+                }
+                self.block_current();
+                return false;
+            });
+            if done {
+                return;
+            }
+        }
+    }
+
+    /// # Wait releasing
+    ///
+    /// Joins the queue, runs `release` and blocks, all with interrupts off. A notify after `release` wakes the thread
+    /// even though it hasn't switched off yet, which is how [`super::Condvar`] gives up its mutex.
+    pub fn wait_releasing(&self, release: impl FnOnce()) -> () {
+        without_interrupts(|| {
+            self.enqueue_current();
+            release();
+            task::block();
+        });
+    }
+
+    /// Wakes the thread that has waited longest, returns false if none was waiting.
+    pub fn notify_one(&self) -> bool {
+        let thread = match self.waiters.lock().pop() {
+            Some(thread) => thread,
+            None => return false,
+        };
+        task::wake(&thread);
+        return true;
+    }
+
+    /// Wakes every waiting thread, returns how many there were.
+    pub fn notify_all(&self) -> usize {
+        let mut count = 0;
+        while self.notify_one() {
+            count += 1;
+        }
+        return count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.lock().is_empty()
+    }
+
+    fn enqueue_current(&self) -> () {
+        assert!(!in_interrupt(), "Waited in an interrupt handler");
+        let current = task::current().expect("Waited before the scheduler was started");
+        if self.waiters.lock().push(current).is_err() {
+            panic!("More than {} threads waiting", MAX_THREADS);
+        }
+    }
+
+    fn block_current(&self) -> () {
+        self.enqueue_current();
+        task::block();
+    }
+}
@@ -0,0 +1,505 @@
+//! # Tasks
+//!
+//! Preemptive scheduling of kernel threads. Each CPU has its own scheduler, threads are picked by the
+//! [`policy`] of their [`SchedulingClass`] and the timer interrupt switches threads when the policy says the current
+//! one should make way. Context switches are done by `switch.asm`.
+//!
+//! [`init`] turns the running code into the first thread and starts scheduling on the current CPU
+//!
+//! [`spawn`] / [`spawn_with_class`] / [`JoinHandle::join`]
+//!
+//! [`yield_now`] / [`sleep`] / [`exit`]
+//!
+//! [`current`] / [`set_class`]
+//!
+//! [`block`] / [`wake`] the scheduler side of [`crate::sync`]
+//!
+//! [`ThreadQueue`] fixed size FIFO of threads, for queues used with interrupts off
+//!
+//! [`preempt`] called from the timer interrupt
+
+pub mod policy;
+pub mod thread;
+
+use crate::{asm, cpu_local, println};
+use crate::clock::{Duration, Instant};
+use crate::interrupts::{interrupts_enabled, without_interrupts};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::RefCell;
+use policy::deadline::DeadlinePolicy;
+use policy::fair::FairPolicy;
+use policy::priority::PriorityPolicy;
+use policy::{Policy, CLASS_COUNT};
+use spin::Mutex;
+pub use policy::SchedulingClass;
+pub use thread::{JoinHandle, Thread, ThreadState};
+
+/// Most threads a CPU can have. The queues are fixed size so the scheduler never allocates with interrupts off.
+pub const MAX_THREADS: usize = 64;
+
+/// How long a thread runs before one of the same priority, or with less fair share run time, gets a turn.
+pub const TIME_SLICE: Duration = Duration::from_millis(10);
+
+extern "C" {
+    fn switch_context(old_stack_pointer: *mut u64, new_stack_pointer: u64) -> ();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// [`init`] hasn't run on this CPU
+    NotStarted,
+    OutOfMemory,
+    /// The CPU already has [`MAX_THREADS`] threads
+    TooManyThreads,
+}
+
+/// # ThreadQueue
+///
+/// Fixed size ring buffer of threads, taken out in the order they were put in. It never allocates, so it can be used
+/// with interrupts off.
+pub struct ThreadQueue {
+    slots: [Option<Arc<Thread>>; MAX_THREADS],
+    head: usize,
+    len: usize,
+}
+
+impl ThreadQueue {
+    const EMPTY: Option<Arc<Thread>> = None;
+
+    pub const fn new() -> ThreadQueue {
+        ThreadQueue {
+            slots: [ThreadQueue::EMPTY; MAX_THREADS],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds a thread to the back, gives it back if the queue already holds [`MAX_THREADS`].
+    pub fn push(&mut self, thread: Arc<Thread>) -> Result<(), Arc<Thread>> {
+        if self.len == MAX_THREADS {
+            return Err(thread);
+        }
+        self.slots[(self.head + self.len) % MAX_THREADS] = Some(thread);
+        self.len += 1;
+        return Ok(());
+    }
+
+    /// Takes the thread at the front.
+    pub fn pop(&mut self) -> Option<Arc<Thread>> {
+        if self.len == 0 {
+            return None;
+        }
+        let thread = self.slots[self.head].take();
+        self.head = (self.head + 1) % MAX_THREADS;
+        self.len -= 1;
+        return thread;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+struct Scheduler {
+    // None until init has run on this CPU
+    current: Option<Arc<Thread>>,
+    // Runs when nothing else can, it is never put in a queue
+    idle: Option<Arc<Thread>>,
+    // When the current thread was switched to
+    switched_in: Option<Instant>,
+    // Threads that haven't been reaped, not counting the idle thread. Kept at most MAX_THREADS so every queue has room
+    thread_count: usize,
+    deadline: DeadlinePolicy,
+    priority: PriorityPolicy,
+    fair: FairPolicy,
+    sleeping: ThreadQueue,
+    // Finished threads whose stacks can't be freed until the scheduler has switched off them
+    finished: ThreadQueue,
+}
+
+impl Scheduler {
+    const fn new() -> Scheduler {
+        Scheduler {
+            current: None,
+            idle: None,
+            switched_in: None,
+            thread_count: 0,
+            deadline: DeadlinePolicy::new(),
+            priority: PriorityPolicy::new(),
+            fair: FairPolicy::new(),
+            sleeping: ThreadQueue::new(),
+            finished: ThreadQueue::new(),
+        }
+    }
+
+    // The policies in rank order
+    fn policies(&mut self) -> [&mut dyn Policy; CLASS_COUNT] {
+        [&mut self.deadline, &mut self.priority, &mut self.fair]
+    }
+
+    fn policy(&mut self, class: SchedulingClass) -> &mut dyn Policy {
+        match class {
+            SchedulingClass::RealTime { .. } => &mut self.deadline,
+            SchedulingClass::Priority(_) => &mut self.priority,
+            SchedulingClass::Fair { .. } => &mut self.fair,
+        }
+    }
+
+    fn enqueue(&mut self, thread: Arc<Thread>, now: Instant, woken: bool) -> () {
+        thread.set_state(ThreadState::Ready);
+        let class = thread.sched().class;
+        self.policy(class).enqueue(thread, now, woken);
+    }
+
+    fn pick_next(&mut self, now: Instant) -> Option<Arc<Thread>> {
+        self.policies().into_iter().find_map(|policy| policy.pick_next(now))
+    }
+
+    // Moves the sleeping threads whose time is up to their policy
+    fn wake_sleepers(&mut self, now: Instant) -> () {
+        for _ in 0..self.sleeping.len {
+            let thread = match self.sleeping.pop() {
+                Some(thread) => thread,
+                None => break,
+            };
+            match thread.state() {
+                ThreadState::Sleeping(deadline) if deadline > now => {
+                    // It just came out of the queue, so there is room
+                    let _ = self.sleeping.push(thread);
+                },
+                _ => self.enqueue(thread, now, true),
+            }
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        match (&self.current, &self.idle) {
+            (Some(current), Some(idle)) => Arc::ptr_eq(current, idle),
+            _ => false,
+        }
+    }
+
+    fn ran(&self, now: Instant) -> Duration {
+        self.switched_in.map(|switched_in| now.duration_since(switched_in)).unwrap_or(Duration::ZERO)
+    }
+
+    // A thread of a higher class is ready, or the current thread's policy wants it switched off
+    fn should_preempt(&mut self, now: Instant) -> bool {
+        let current = match self.current.clone() {
+            Some(current) => current,
+            None => return false,
+        };
+        let is_idle = self.is_idle();
+        let class = current.sched().class;
+        let ran = self.ran(now);
+        for (rank, policy) in self.policies().into_iter().enumerate() {
+            if is_idle || rank < class.rank() {
+                if !policy.is_empty() {
+                    return true;
+                }
+            } else if rank == class.rank() {
+                return policy.should_preempt(&current, ran, now);
+            }
+        }
+        return false;
+    }
+}
+
+cpu_local! {
+    static SCHEDULER: RefCell<Scheduler> = RefCell::new(Scheduler::new());
+}
+
+/// # Init
+///
+/// Starts scheduling on the current CPU. The code calling this becomes the "kernel main" thread, which keeps the stack it
+/// is running on.
+pub fn init() -> () {
+    let main = Arc::new(Thread::bootstrap("kernel main"));
+    let idle = Thread::new("idle", SchedulingClass::default(), Box::new(idle_main))
+        .expect("Failed to allocate the idle thread's stack");
+    let idle = Arc::new(idle);
+    without_interrupts(|| {
+        let mut scheduler = SCHEDULER.get().borrow_mut();
+        scheduler.current = Some(main);
+        scheduler.idle = Some(idle);
+        scheduler.switched_in = Some(Instant::now());
+        scheduler.thread_count = 1;
+    });
+    println!(0x0022FF22; "-- Started the scheduler with {:?} time slices", TIME_SLICE);
+}
+
+fn idle_main() -> () {
+    loop {
+        asm::hlt();
+    }
+}
+
+/// Starts a kernel thread in the default fair share class, see [`spawn_with_class`].
+pub fn spawn<T, F>(name: &str, f: F) -> Result<JoinHandle<T>, SpawnError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    spawn_with_class(name, SchedulingClass::default(), f)
+}
+
+/// # Spawn with class
+///
+/// Starts a kernel thread on the current CPU, it runs once its policy picks it.
+///
+/// ## Arguments
+/// * 'name' - shown when something goes wrong in the thread
+/// * 'class' - how the thread is scheduled
+/// * 'f' - what the thread runs, its return value is handed to [`JoinHandle::join`]
+///
+/// ## Returns
+/// * 'Result<JoinHandle<T>, SpawnError>' - A handle to wait for the thread with, dropping it leaves the thread running
+pub fn spawn_with_class<T, F>(name: &str, class: SchedulingClass, f: F) -> Result<JoinHandle<T>, SpawnError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    // Counted first so no other spawn can take the slot while the stack is allocated
+    without_interrupts(|| {
+        let mut scheduler = SCHEDULER.get().borrow_mut();
+        if scheduler.current.is_none() {
+            return Err(SpawnError::NotStarted);
+        }
+        if scheduler.thread_count >= MAX_THREADS {
+            return Err(SpawnError::TooManyThreads);
+        }
+        scheduler.thread_count += 1;
+        return Ok(());
+    })?;
+
+    let result = Arc::new(Mutex::new(None));
+    let slot = result.clone();
+    let entry = Box::new(move || {
+        let value = f();
+        *slot.lock() = Some(value);
+    });
+    let thread = match Thread::new(name, class, entry) {
+        Some(thread) => Arc::new(thread),
+        None => {
+            without_interrupts(|| SCHEDULER.get().borrow_mut().thread_count -= 1);
+            return Err(SpawnError::OutOfMemory);
+        },
+    };
+
+    without_interrupts(|| SCHEDULER.get().borrow_mut().enqueue(thread.clone(), Instant::now(), true));
+    return Ok(JoinHandle::new(thread, result));
+}
+
+/// The thread running on this CPU, None before [`init`].
+pub fn current() -> Option<Arc<Thread>> {
+    without_interrupts(|| SCHEDULER.get().borrow().current.clone())
+}
+
+/// # Set class
+///
+/// Moves a thread of the current CPU to another scheduling class. A queued thread is requeued as if it had just woken
+/// up, the running thread is switched off at the next timer tick if a higher class has a thread ready.
+pub fn set_class(thread: &Arc<Thread>, class: SchedulingClass) -> () {
+    without_interrupts(|| {
+        let mut scheduler = SCHEDULER.get().borrow_mut();
+        let now = Instant::now();
+        let old_class = thread.sched().class;
+        let queued = scheduler.policy(old_class).remove(thread);
+
+        {
+            let mut entity = thread.sched();
+            entity.class = class;
+            if let SchedulingClass::RealTime { relative_deadline } = class {
+                entity.deadline = now + relative_deadline;
+            }
+        }
+        if let Some(queued) = queued {
+            scheduler.enqueue(queued, now, true);
+        }
+    });
+}
+
+/// Lets the scheduler pick again, the current thread keeps running if its policy still picks it first.
+pub fn yield_now() -> () {
+    schedule();
+    reap();
+}
+
+/// # Sleep
+///
+/// Blocks the current thread until at least `duration` has passed. Before [`init`] it halts until then instead.
+pub fn sleep(duration: Duration) -> () {
+    let deadline = Instant::now() + duration;
+    if current().is_none() {
+        while Instant::now() < deadline {
+            asm::hlt();
+        }
+        return;
+    }
+
+    // The state and the switch have to happen together, so the timer can't wake the thread in between
+    without_interrupts(|| {
+        if let Some(current) = &SCHEDULER.get().borrow().current {
+            current.set_state(ThreadState::Sleeping(deadline));
+        }
+        schedule();
+    });
+    reap();
+}
+
+/// # Block
+///
+/// Switches off the current thread until [`wake`] is called on it. Interrupts must already be off, and stay off from
+/// before the thread was made visible to whatever wakes it, otherwise the wake up could come first and be lost.
+pub fn block() -> () {
+    assert!(!interrupts_enabled(), "Blocked a thread with interrupts on");
+    match &SCHEDULER.get().borrow().current {
+        Some(current) => current.set_state(ThreadState::Blocked),
+        None => panic!("Blocked before the scheduler was started"),
+    }
+    schedule();
+}
+
+/// # Wake
+///
+/// Makes a thread blocked by [`block`] ready again, it is queued on the current CPU. Safe to call from interrupt
+/// handlers.
+///
+/// ## Returns
+/// * 'bool' - false if the thread wasn't blocked
+pub fn wake(thread: &Arc<Thread>) -> bool {
+    without_interrupts(|| {
+        if thread.state() != ThreadState::Blocked {
+            return false;
+        }
+        let mut scheduler = SCHEDULER.get().borrow_mut();
+        let is_current = match &scheduler.current {
+            Some(current) => Arc::ptr_eq(current, thread),
+            None => false,
+        };
+        if is_current {
+            // It hasn't been switched off yet
+            thread.set_state(ThreadState::Running);
+        } else {
+            scheduler.enqueue(thread.clone(), Instant::now(), true);
+        }
+        return true;
+    })
+}
+
+/// Ends the current thread and wakes the threads joining it, its stack is freed by the next thread to run.
+pub fn exit() -> ! {
+    without_interrupts(|| {
+        let current = current().expect("Exited a thread before the scheduler was started");
+        current.set_state(ThreadState::Finished);
+        current.joiners().notify_all();
+        // Nothing may keep the thread alive from its own stack, it is never returned to
+        drop(current);
+        schedule();
+    });
+    unreachable!("Switched back to a finished thread");
+}
+
+/// # Preempt
+///
+/// Wakes the sleeping threads that are due and switches threads if the current one should make way, called by the timer
+/// interrupt once the interrupt has been acknowledged. A thread holding a spinlock would leave every other thread that
+/// wants it spinning, so kernel wide spinlocks are [`crate::sync::IrqSpinLock`]s, which keep the timer off while held.
+pub fn preempt() -> () {
+    let reschedule = {
+        let mut scheduler = SCHEDULER.get().borrow_mut();
+        let now = Instant::now();
+        scheduler.wake_sleepers(now);
+        scheduler.should_preempt(now)
+    };
+    if reschedule {
+        schedule();
+    }
+}
+
+// Switches to the next thread the policies pick. The current one is handed back to its policy if it is still running,
+// otherwise it goes to the sleeping or finished threads. Nothing here allocates or frees since it can run in the timer
+// interrupt.
+fn schedule() -> () {
+    without_interrupts(|| {
+        let (old_stack_pointer, new_stack_pointer) = {
+            let mut scheduler = SCHEDULER.get().borrow_mut();
+            let current = match scheduler.current.clone() {
+                Some(current) => current,
+                None => return,
+            };
+            let now = Instant::now();
+            scheduler.wake_sleepers(now);
+
+            let is_idle = scheduler.is_idle();
+            if !is_idle {
+                let ran = scheduler.ran(now);
+                let class = current.sched().class;
+                scheduler.policy(class).account(&current, ran);
+            }
+            scheduler.switched_in = Some(now);
+
+            // There is always room in the queues, the thread count is limited to their size
+            match current.state() {
+                ThreadState::Running if is_idle => current.set_state(ThreadState::Ready),
+                ThreadState::Running => scheduler.enqueue(current.clone(), now, false),
+                ThreadState::Sleeping(_) => {
+                    let _ = scheduler.sleeping.push(current.clone());
+                },
+                ThreadState::Finished => {
+                    let _ = scheduler.finished.push(current.clone());
+                },
+                // Whatever will wake it keeps it alive until then
+                ThreadState::Blocked | ThreadState::Ready => {},
+            }
+
+            let next = match scheduler.pick_next(now) {
+                Some(next) => next,
+                None => scheduler.idle.clone().expect("Scheduler has no idle thread"),
+            };
+            next.set_state(ThreadState::Running);
+            scheduler.current = Some(next.clone());
+            if Arc::ptr_eq(&next, &current) {
+                return;
+            }
+            // The queues keep both threads alive, no reference may be left on a stack that might never be returned to
+            (current.stack_pointer(), unsafe { *next.stack_pointer() })
+        };
+
+        unsafe {
+            switch_context(old_stack_pointer, new_stack_pointer);
+        }
+    });
+}
+
+// Drops the finished threads, which frees their stacks. Only called outside interrupt handlers since freeing takes locks
+// the interrupted thread might hold.
+fn reap() -> () {
+    loop {
+        let thread = without_interrupts(|| {
+            let mut scheduler = SCHEDULER.get().borrow_mut();
+            let thread = scheduler.finished.pop();
+            if thread.is_some() {
+                scheduler.thread_count -= 1;
+            }
+            thread
+        });
+        match thread {
+            Some(thread) => drop(thread),
+            None => break,
+        }
+    }
+}
+
+// Where every new thread starts, switch_context returns into thread_start which calls this.
+#[no_mangle]
+extern "C" fn thread_main() -> ! {
+    // Threads start with interrupts off, the scheduler that switched here had them off
+    asm::sti();
+    reap();
+    if let Some(entry) = current().and_then(|thread| thread.take_entry()) {
+        entry();
+    }
+    exit();
+}
@@ -0,0 +1,56 @@
+//! # Earliest deadline first
+//!
+//! [`DeadlinePolicy`] the real-time class
+
+use super::{Policy, SchedulingClass, ThreadSet};
+use crate::clock::{Duration, Instant};
+use crate::task::Thread;
+use alloc::sync::Arc;
+
+/// # DeadlinePolicy
+///
+/// Runs the thread with the earliest deadline. Real-time threads have no time slice, one runs until it blocks, yields or
+/// a thread with an earlier deadline wakes up.
+pub struct DeadlinePolicy {
+    threads: ThreadSet,
+}
+
+impl DeadlinePolicy {
+    pub const fn new() -> DeadlinePolicy {
+        DeadlinePolicy { threads: ThreadSet::new() }
+    }
+}
+
+impl Policy for DeadlinePolicy {
+    fn enqueue(&mut self, thread: Arc<Thread>, now: Instant, woken: bool) -> () {
+        if woken {
+            let mut entity = thread.sched();
+            if let SchedulingClass::RealTime { relative_deadline } = entity.class {
+                entity.deadline = now + relative_deadline;
+            }
+        }
+        self.threads.insert(thread);
+    }
+
+    fn pick_next(&mut self, _now: Instant) -> Option<Arc<Thread>> {
+        self.threads.take_min_by_key(|thread| thread.sched().deadline)
+    }
+
+    fn remove(&mut self, thread: &Thread) -> Option<Arc<Thread>> {
+        self.threads.remove(thread)
+    }
+
+    fn account(&mut self, _thread: &Thread, _ran: Duration) -> () {}
+
+    fn should_preempt(&self, current: &Thread, _ran: Duration, _now: Instant) -> bool {
+        let deadline = current.sched().deadline;
+        match self.threads.min_key(|thread| thread.sched().deadline) {
+            Some(earliest) => earliest < deadline,
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+}
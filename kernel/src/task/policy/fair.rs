@@ -0,0 +1,78 @@
+//! # Fair share
+//!
+//! [`FairPolicy`] the fair share class, the default
+
+use super::{Policy, SchedulingClass, ThreadSet, DEFAULT_WEIGHT};
+use crate::clock::{Duration, Instant};
+use crate::task::{Thread, TIME_SLICE};
+use alloc::sync::Arc;
+use kernel_lib::sched::weighted_runtime;
+
+/// # FairPolicy
+///
+/// Runs the thread that has had the least run time scaled by its weight, its virtual run time. Over time every thread
+/// gets CPU time in proportion to its weight. A thread that wakes up starts level with the queued ones, so sleeping
+/// doesn't save up time.
+pub struct FairPolicy {
+    threads: ThreadSet,
+    // Virtual run time of the last thread picked, never goes down
+    min_vruntime: u64,
+}
+
+impl FairPolicy {
+    pub const fn new() -> FairPolicy {
+        FairPolicy {
+            threads: ThreadSet::new(),
+            min_vruntime: 0,
+        }
+    }
+}
+
+fn weight(class: SchedulingClass) -> u32 {
+    match class {
+        SchedulingClass::Fair { weight } => weight,
+        _ => DEFAULT_WEIGHT,
+    }
+}
+
+impl Policy for FairPolicy {
+    fn enqueue(&mut self, thread: Arc<Thread>, _now: Instant, woken: bool) -> () {
+        if woken {
+            let mut entity = thread.sched();
+            entity.vruntime = entity.vruntime.max(self.min_vruntime);
+        }
+        self.threads.insert(thread);
+    }
+
+    fn pick_next(&mut self, _now: Instant) -> Option<Arc<Thread>> {
+        let thread = self.threads.take_min_by_key(|thread| thread.sched().vruntime)?;
+        self.min_vruntime = self.min_vruntime.max(thread.sched().vruntime);
+        return Some(thread);
+    }
+
+    fn remove(&mut self, thread: &Thread) -> Option<Arc<Thread>> {
+        self.threads.remove(thread)
+    }
+
+    fn account(&mut self, thread: &Thread, ran: Duration) -> () {
+        let mut entity = thread.sched();
+        entity.vruntime += weighted_runtime(ran.as_nanos() as u64, weight(entity.class));
+    }
+
+    fn should_preempt(&self, current: &Thread, ran: Duration, _now: Instant) -> bool {
+        if ran < TIME_SLICE {
+            return false;
+        }
+        // The current thread's virtual run time as if it were charged now
+        let entity = current.sched();
+        let vruntime = entity.vruntime + weighted_runtime(ran.as_nanos() as u64, weight(entity.class));
+        match self.threads.min_key(|thread| thread.sched().vruntime) {
+            Some(lowest) => lowest < vruntime,
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+}
@@ -0,0 +1,181 @@
+//! # Scheduling policies
+//!
+//! Every thread belongs to a [`SchedulingClass`], chosen when it is spawned and changeable with
+//! [`super::set_class`]. Each class has a [`Policy`] holding its ready threads. The classes are ranked, a class only
+//! runs while the ones above it have nothing ready:
+//!
+//! [`deadline::DeadlinePolicy`] real-time threads, earliest deadline first
+//!
+//! [`priority::PriorityPolicy`] fixed priorities raised by waiting
+//!
+//! [`fair::FairPolicy`] CPU time shared by weight
+//!
+//! [`ThreadSet`] the fixed size storage the policies keep their threads in
+
+pub mod deadline;
+pub mod fair;
+pub mod priority;
+
+use super::{Thread, MAX_THREADS};
+use crate::clock::{Duration, Instant};
+use alloc::sync::Arc;
+pub use kernel_lib::sched::DEFAULT_WEIGHT;
+
+/// Number of scheduling classes, [`SchedulingClass::rank`] goes from 0 to one less than this.
+pub const CLASS_COUNT: usize = 3;
+
+/// # SchedulingClass
+///
+/// How a thread competes for the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulingClass {
+    /// Runs ahead of every other class, earliest deadline first, until it blocks or a thread with an earlier deadline
+    /// is ready. The deadline is set `relative_deadline` after the thread wakes up, meant for interrupt bottom halves
+    /// and other short latency sensitive work.
+    RealTime { relative_deadline: Duration },
+    /// Fixed priority from 0 to [`priority::MAX_PRIORITY`], higher runs first. Waiting raises it so lower priorities
+    /// still run.
+    Priority(u8),
+    /// Gets CPU time in proportion to `weight`, [`DEFAULT_WEIGHT`] is an ordinary share.
+    Fair { weight: u32 },
+}
+
+impl SchedulingClass {
+    /// Position of the class, 0 runs first.
+    pub fn rank(&self) -> usize {
+        match self {
+            SchedulingClass::RealTime { .. } => 0,
+            SchedulingClass::Priority(_) => 1,
+            SchedulingClass::Fair { .. } => 2,
+        }
+    }
+}
+
+impl Default for SchedulingClass {
+    fn default() -> SchedulingClass {
+        SchedulingClass::Fair { weight: DEFAULT_WEIGHT }
+    }
+}
+
+/// # SchedEntity
+///
+/// A thread's scheduling state. The policies only look at the fields of their own class.
+#[derive(Debug, Clone, Copy)]
+pub struct SchedEntity {
+    pub class: SchedulingClass,
+    /// Real-time deadline, set when the thread wakes
+    pub deadline: Instant,
+    /// Fair share run time scaled by the weight, in nanoseconds
+    pub vruntime: u64,
+    /// When the thread last went into a queue
+    pub ready_since: Instant,
+}
+
+impl SchedEntity {
+    pub fn new(class: SchedulingClass) -> SchedEntity {
+        let now = Instant::now();
+        SchedEntity {
+            class: class,
+            deadline: now,
+            vruntime: 0,
+            ready_since: now,
+        }
+    }
+}
+
+/// # Policy
+///
+/// The ready threads of a scheduling class. The scheduler calls these with interrupts off, so they must not allocate.
+/// The current thread is never queued, it is handed back with [`Policy::enqueue`] when it stops running.
+pub trait Policy {
+    /// # Enqueue
+    ///
+    /// Adds a ready thread.
+    ///
+    /// ## Arguments
+    /// * 'thread' - a thread of this class
+    /// * 'now' - the current time
+    /// * 'woken' - true if the thread is new or was blocked, false if it was preempted or yielded
+    fn enqueue(&mut self, thread: Arc<Thread>, now: Instant, woken: bool) -> ();
+
+    /// Takes out the thread that should run next.
+    fn pick_next(&mut self, now: Instant) -> Option<Arc<Thread>>;
+
+    /// Takes a thread out of the queue, None if it wasn't in it.
+    fn remove(&mut self, thread: &Thread) -> Option<Arc<Thread>>;
+
+    /// Charges a thread of this class for `ran`, called when it stops running.
+    fn account(&mut self, thread: &Thread, ran: Duration) -> ();
+
+    /// # Should preempt
+    ///
+    /// Called every timer tick while a thread of this class runs.
+    ///
+    /// ## Arguments
+    /// * 'current' - the running thread
+    /// * 'ran' - how long it has run since it was switched to
+    /// * 'now' - the current time
+    ///
+    /// ## Returns
+    /// * 'bool' - true if a queued thread should take over
+    fn should_preempt(&self, current: &Thread, ran: Duration, now: Instant) -> bool;
+
+    fn is_empty(&self) -> bool;
+}
+
+/// # ThreadSet
+///
+/// Unordered fixed size set of threads, the policies search it for the next thread. With at most [`MAX_THREADS`]
+/// threads a scan is cheap enough for the timer interrupt.
+pub struct ThreadSet {
+    slots: [Option<Arc<Thread>>; MAX_THREADS],
+    len: usize,
+}
+
+impl ThreadSet {
+    const EMPTY: Option<Arc<Thread>> = None;
+
+    pub const fn new() -> ThreadSet {
+        ThreadSet {
+            slots: [ThreadSet::EMPTY; MAX_THREADS],
+            len: 0,
+        }
+    }
+
+    /// Adds a thread. The scheduler limits how many threads there are, so there is always room.
+    pub fn insert(&mut self, thread: Arc<Thread>) -> () {
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(thread),
+            None => panic!("More than {} threads queued", MAX_THREADS),
+        }
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, thread: &Thread) -> Option<Arc<Thread>> {
+        let slot = self.slots.iter_mut().find(|slot| match slot {
+            Some(queued) => core::ptr::eq(Arc::as_ptr(queued), thread),
+            None => false,
+        })?;
+        self.len -= 1;
+        return slot.take();
+    }
+
+    /// The smallest key of the queued threads, ties go to the first one found.
+    pub fn min_key<K: Ord>(&self, key: impl Fn(&Thread) -> K) -> Option<K> {
+        self.slots.iter().flatten().map(|thread| key(thread)).min()
+    }
+
+    /// Takes out the thread with the smallest key.
+    pub fn take_min_by_key<K: Ord>(&mut self, key: impl Fn(&Thread) -> K) -> Option<Arc<Thread>> {
+        let slot = self.slots.iter_mut().filter(|slot| slot.is_some()).min_by_key(|slot| match slot {
+            Some(thread) => Some(key(thread)),
+            None => None,
+        })?;
+        self.len -= 1;
+        return slot.take();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
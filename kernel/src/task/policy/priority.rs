@@ -0,0 +1,79 @@
+//! # Fixed priorities with aging
+//!
+//! [`PriorityPolicy`] the priority class
+//!
+//! [`MAX_PRIORITY`] / [`AGING_INTERVAL`]
+
+use super::{Policy, SchedEntity, SchedulingClass, ThreadSet};
+use crate::clock::{Duration, Instant};
+use crate::task::{Thread, TIME_SLICE};
+use alloc::sync::Arc;
+use core::cmp::Reverse;
+use kernel_lib::sched::aged_priority;
+
+/// Highest priority, waiting never raises a thread above it.
+pub const MAX_PRIORITY: u8 = 31;
+
+/// How long a thread waits for each step its priority is raised.
+pub const AGING_INTERVAL: Duration = Duration::from_millis(50);
+
+/// # PriorityPolicy
+///
+/// Runs the thread with the highest priority, threads with the same one take turns a time slice at a time. A queued
+/// thread's priority goes up by one every [`AGING_INTERVAL`] it waits and drops back once it runs, so a busy high
+/// priority thread can't starve the rest.
+pub struct PriorityPolicy {
+    threads: ThreadSet,
+}
+
+impl PriorityPolicy {
+    pub const fn new() -> PriorityPolicy {
+        PriorityPolicy { threads: ThreadSet::new() }
+    }
+}
+
+fn base_priority(entity: &SchedEntity) -> u8 {
+    match entity.class {
+        SchedulingClass::Priority(priority) => priority,
+        _ => 0,
+    }
+}
+
+// The priority the thread competes with after waiting
+fn effective_priority(entity: &SchedEntity, now: Instant) -> u8 {
+    let waited = now.duration_since(entity.ready_since).as_nanos() as u64;
+    aged_priority(base_priority(entity), waited, AGING_INTERVAL.as_nanos() as u64, MAX_PRIORITY)
+}
+
+impl Policy for PriorityPolicy {
+    fn enqueue(&mut self, thread: Arc<Thread>, now: Instant, _woken: bool) -> () {
+        thread.sched().ready_since = now;
+        self.threads.insert(thread);
+    }
+
+    fn pick_next(&mut self, now: Instant) -> Option<Arc<Thread>> {
+        // Highest priority first, then whoever has waited longest
+        self.threads.take_min_by_key(|thread| {
+            let entity = thread.sched();
+            (Reverse(effective_priority(&entity, now)), entity.ready_since)
+        })
+    }
+
+    fn remove(&mut self, thread: &Thread) -> Option<Arc<Thread>> {
+        self.threads.remove(thread)
+    }
+
+    fn account(&mut self, _thread: &Thread, _ran: Duration) -> () {}
+
+    fn should_preempt(&self, current: &Thread, ran: Duration, now: Instant) -> bool {
+        let priority = base_priority(&current.sched());
+        match self.threads.min_key(|thread| Reverse(effective_priority(&thread.sched(), now))) {
+            Some(Reverse(highest)) => highest > priority || (highest == priority && ran >= TIME_SLICE),
+            None => false,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+}
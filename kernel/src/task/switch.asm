@@ -0,0 +1,31 @@
+[bits 64]
+
+; switch_context(old_stack_pointer, new_stack_pointer)
+; Saves the callee saved registers and flags on the current stack, stores RSP in *old_stack_pointer and resumes the
+; thread whose saved RSP is new_stack_pointer. The call returns once something switches back to the old thread.
+switch_context:
+   PUSH RBX
+   PUSH RBP
+   PUSH R12
+   PUSH R13
+   PUSH R14
+   PUSH R15
+   PUSHFQ
+   MOV [RDI], RSP
+   MOV RSP, RSI
+   POPFQ
+   POP R15
+   POP R14
+   POP R13
+   POP R12
+   POP RBP
+   POP RBX
+   RET
+
+; First code a new thread runs, Thread::new sets up its stack so switch_context returns here
+thread_start:
+   CALL thread_main
+   UD2               ; thread_main never returns
+
+extern thread_main
+global switch_context, thread_start
@@ -0,0 +1,199 @@
+//! # Kernel threads
+//!
+//! [`Thread`] a schedulable flow of execution with its own stack
+//!
+//! [`JoinHandle`] waits for a thread from [`super::spawn`] and collects its result
+
+use super::policy::{SchedEntity, SchedulingClass};
+use crate::clock::Instant;
+use crate::paging::stack::{self, KernelStack};
+use crate::sync::{IrqSpinLock, IrqSpinLockGuard, WaitQueue};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+
+/// Size of a thread's stack in pages.
+pub const THREAD_STACK_PAGES: u64 = 16;
+
+// Bit 1 of RFLAGS is always set, interrupts start off and thread_main turns them on
+const INITIAL_RFLAGS: u64 = 0x2;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+extern "C" {
+    fn thread_start() -> ();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    /// Waiting in a run queue
+    Ready,
+    Running,
+    /// Not runnable until the instant has passed
+    Sleeping(Instant),
+    /// Not runnable until something calls [`super::wake`] on it, normally a [`crate::sync::WaitQueue`]
+    Blocked,
+    /// Returned from its entry point, its stack is freed once the scheduler is off it
+    Finished,
+}
+
+/// # Thread
+///
+/// A kernel thread. While it isn't running everything needed to resume it is saved on its own stack by
+/// `switch_context`, the thread only keeps the stack pointer.
+pub struct Thread {
+    id: u64,
+    name: String,
+    // None for the thread that was already running when the scheduler started, it keeps the stack it had
+    stack: Option<KernelStack>,
+    stack_pointer: UnsafeCell<u64>,
+    // Both are read by the scheduler in the timer interrupt
+    state: IrqSpinLock<ThreadState>,
+    sched: IrqSpinLock<SchedEntity>,
+    entry: Mutex<Option<Box<dyn FnOnce() + Send + 'static>>>,
+    finished: AtomicBool,
+    // Threads in JoinHandle::join
+    joiners: WaitQueue,
+}
+
+// The stack pointer is only touched by the scheduler of the CPU the thread belongs to, with interrupts off
+unsafe impl Sync for Thread {}
+
+impl Thread {
+    /// # New
+    ///
+    /// Allocates a stack and sets it up as if the thread had been switched away from just before `thread_start`, so the
+    /// first switch to it runs `entry`.
+    ///
+    /// ## Arguments
+    /// * 'name' - shown when something goes wrong in the thread
+    /// * 'class' - how the thread is scheduled
+    /// * 'entry' - what the thread runs
+    ///
+    /// ## Returns
+    /// * 'Option<Thread>' - None if the stack couldn't be allocated
+    pub fn new(name: &str, class: SchedulingClass, entry: Box<dyn FnOnce() + Send + 'static>) -> Option<Thread> {
+        let stack = stack::alloc_stack(THREAD_STACK_PAGES, "kernel thread")?;
+        // What switch_context pops: RFLAGS, R15, R14, R13, R12, RBP, RBX and the return address. The stack top is
+        // 16 byte aligned, so thread_start calls thread_main with the alignment the ABI expects
+        let frame: [u64; 8] = [INITIAL_RFLAGS, 0, 0, 0, 0, 0, 0, thread_start as u64];
+        let stack_pointer = stack.top - core::mem::size_of_val(&frame) as u64;
+        unsafe {
+            core::ptr::write(stack_pointer as *mut [u64; 8], frame);
+        }
+
+        return Some(Thread {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: String::from(name),
+            stack: Some(stack),
+            stack_pointer: UnsafeCell::new(stack_pointer),
+            state: IrqSpinLock::new(ThreadState::Ready),
+            sched: IrqSpinLock::new(SchedEntity::new(class)),
+            entry: Mutex::new(Some(entry)),
+            finished: AtomicBool::new(false),
+            joiners: WaitQueue::new(),
+        });
+    }
+
+    /// Wraps the code that is already running, so the scheduler can switch away from it and back.
+    pub fn bootstrap(name: &str) -> Thread {
+        Thread {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: String::from(name),
+            stack: None,
+            stack_pointer: UnsafeCell::new(0),
+            state: IrqSpinLock::new(ThreadState::Running),
+            sched: IrqSpinLock::new(SchedEntity::new(SchedulingClass::default())),
+            entry: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            joiners: WaitQueue::new(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> ThreadState {
+        *self.state.lock()
+    }
+
+    pub(super) fn set_state(&self, state: ThreadState) -> () {
+        *self.state.lock() = state;
+        if state == ThreadState::Finished {
+            self.finished.store(true, Ordering::Release);
+        }
+    }
+
+    /// The class the thread is scheduled in, change it with [`super::set_class`].
+    pub fn class(&self) -> SchedulingClass {
+        self.sched.lock().class
+    }
+
+    pub(super) fn sched(&self) -> IrqSpinLockGuard<'_, SchedEntity> {
+        self.sched.lock()
+    }
+
+    /// Returns true once the thread's entry point has returned.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    pub(super) fn stack_pointer(&self) -> *mut u64 {
+        self.stack_pointer.get()
+    }
+
+    pub(super) fn joiners(&self) -> &WaitQueue {
+        &self.joiners
+    }
+
+    pub(super) fn take_entry(&self) -> Option<Box<dyn FnOnce() + Send + 'static>> {
+        self.entry.lock().take()
+    }
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        if let Some(stack) = self.stack.take() {
+            stack::free_stack(stack);
+        }
+    }
+}
+
+/// # JoinHandle
+///
+/// Returned by [`super::spawn`], dropping it detaches the thread.
+pub struct JoinHandle<T> {
+    thread: Arc<Thread>,
+    result: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub(super) fn new(thread: Arc<Thread>, result: Arc<Mutex<Option<T>>>) -> JoinHandle<T> {
+        JoinHandle {
+            thread: thread,
+            result: result,
+        }
+    }
+
+    pub fn thread(&self) -> &Arc<Thread> {
+        &self.thread
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until the thread has finished, then returns what its entry point returned.
+    pub fn join(self) -> T {
+        self.thread.joiners().wait_until(|| self.thread.is_finished());
+        return self.result.lock().take().expect("Joined thread left no result");
+    }
+}
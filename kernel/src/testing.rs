@@ -0,0 +1,63 @@
+//! # In kernel test framework
+//! 
+//! Runs the `#[test_case]` functions collected by `custom_test_frameworks` after the kernel has initialised, then exits
+//! qemu through the isa-debug-exit device so `cargo test` sees a pass or fail. Results are printed with println!, which
+//! mirrors them to the serial port.
+//! 
+//! [`Testable`]
+//! 
+//! [`test_runner`]
+//! 
+//! [`exit_qemu`]
+
+use crate::{asm, print, println};
+
+// Port of qemu's isa-debug-exit device, see scripts/qemu_test.sh
+const ISA_DEBUG_EXIT_PORT: u16 = 0xF4;
+
+/// Codes written to the isa-debug-exit port, qemu exits with `(code << 1) | 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+/// A test that prints its own name before running.
+pub trait Testable {
+    fn run(&self) -> ();
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) -> () {
+        print!("{}... ", core::any::type_name::<T>());
+        self();
+        println!(0x0022FF22; "[ok]");
+    }
+}
+
+/// Runs every test, a failing test panics and never returns here.
+pub fn test_runner(tests: &[&dyn Testable]) -> () {
+    println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    exit_qemu(QemuExitCode::Success);
+}
+
+/// Reports the failing test and exits qemu.
+pub fn test_panic_handler(info: &core::panic::PanicInfo) -> ! {
+    println!(0x00FF2222; "[failed]");
+    println!("{}", info);
+    exit_qemu(QemuExitCode::Failed);
+}
+
+/// # Exit qemu
+/// 
+/// Writes the exit code to the isa-debug-exit port, if the device is missing the CPU is halted instead.
+pub fn exit_qemu(exit_code: QemuExitCode) -> ! {
+    asm::outl(ISA_DEBUG_EXIT_PORT, exit_code as u32);
+    loop {
+        asm::hlt();
+    }
+}
@@ -0,0 +1,112 @@
+//boolean array but each bool uses 1 bit
+pub struct Bitmap {
+    pub length: u64,
+    pub bitmap_ptr: *mut u8,
+}
+
+impl Bitmap {
+    /// # New
+    ///
+    /// Clears `size` bytes at `start_addr` and uses them as the bitmap.
+    ///
+    /// # Safety
+    /// `start_addr` must be valid for reads and writes of `size` bytes for as long as the bitmap is used, and nothing
+    /// else may access that memory in the meantime.
+    pub unsafe fn new(start_addr: *mut u8, size: u64) -> Bitmap {
+        for i in 0..size {
+            *start_addr.offset(i as isize) = 0x00;
+        }
+        Bitmap {
+            length: size,
+            bitmap_ptr: start_addr,
+        }
+    }
+
+    // Sets every bit in the bitmap to value
+    pub fn fill(&mut self, value: bool) -> () {
+        let byte = if value { 0xFF } else { 0x00 };
+        unsafe {
+            for i in 0..self.length {
+                *self.bitmap_ptr.offset(i as isize) = byte;
+            }
+        }
+    }
+
+    unsafe fn byte_from_index(&self, index: u64) -> *mut u8 {
+        self.bitmap_ptr.offset((index / 8) as isize)
+    }
+
+    // Gets the state of the bit at index
+    pub fn get_bit(&mut self, index: u64) -> bool {
+        if index < self.length * 8 {
+            unsafe {
+                let byte = self.byte_from_index(index);
+                return (*byte & (1 << index % 8)) > 0;
+            }
+        } else {
+            panic!("Index out of bounds of bitmap");
+        }
+    }
+
+    // Attempts to set the bit at index, returns false if the index was out of bounds of the bitmap
+    pub fn set_bit(&mut self, index: u64) -> bool {
+        if index < self.length * 8 {
+            unsafe {
+                *(self.byte_from_index(index)) = *(self.byte_from_index(index)) | 1 << index % 8;
+            }
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    // Attempts to set the bit at index, returns false if the index was out of bounds of the bitmap
+    pub fn clear_bit(&mut self, index: u64) -> bool {
+        if index < self.length * 8 {
+            unsafe {
+                *(self.byte_from_index(index)) = *(self.byte_from_index(index)) & !(1 << index % 8);
+            }
+            return true;
+        } else {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_bitmap_is_clear() {
+        let mut buffer = [0xFFu8; 4];
+        let mut bitmap = unsafe { Bitmap::new(buffer.as_mut_ptr(), 4) };
+        for i in 0..32 {
+            assert!(!bitmap.get_bit(i));
+        }
+    }
+
+    #[test]
+    fn set_and_clear_bits() {
+        let mut buffer = [0u8; 4];
+        let mut bitmap = unsafe { Bitmap::new(buffer.as_mut_ptr(), 4) };
+        assert!(bitmap.set_bit(9));
+        assert!(bitmap.get_bit(9));
+        assert!(!bitmap.get_bit(8));
+        assert!(bitmap.clear_bit(9));
+        assert!(!bitmap.get_bit(9));
+        assert!(!bitmap.set_bit(32));
+        assert!(!bitmap.clear_bit(32));
+        assert_eq!(buffer, [0; 4]);
+    }
+
+    #[test]
+    fn fill_sets_every_bit() {
+        let mut buffer = [0u8; 4];
+        let mut bitmap = unsafe { Bitmap::new(buffer.as_mut_ptr(), 4) };
+        bitmap.fill(true);
+        assert!(bitmap.get_bit(0) && bitmap.get_bit(31));
+        bitmap.fill(false);
+        assert!(!bitmap.get_bit(31));
+    }
+}
@@ -0,0 +1,149 @@
+//! # Segment descriptor encoding
+//! 
+//! [`Segment`] a code or data segment descriptor
+//! 
+//! [`SysSegment`] a long mode system segment descriptor, used for the TSS
+
+/// The segemnt descriptor, Describes each segment in the [`GDT`].\ 
+/// Details at https://wiki.osdev.org/Global_descriptor_table#Segment_Descriptor.
+/// 
+/// The basic table:
+/// 
+/// | 63   | 56  |\|| 55  | 52   |\|| 51  | 48   |\|| 47   | 40        |\|| 39  | 32  |
+/// | :--  | --: |--| :-- | --:  |--| :-- | --:  |--| :--  | --:       |--| :-- | --: | 
+/// | **Base**  ||\|| **Flags** ||\|| **Limit** ||\|| **Access Byte** ||\|| **Base** ||
+/// | 31   | 24  |\|| 3   | 0    |\|| 19  | 16   |\|| 7    | 0         |\|| 23  | 16  |
+/// |**31**|     |  |     |      |  |     |**16**|\||**15**|           |  |     |**0**|
+/// | **Base**  ||  |     |      |  |     |      |\|| **Limit**       ||  |     |     |
+/// | 15   |     |  |     |      |  |     | 0    |\|| 15   |           |  |     | 0   |
+pub struct Segment {
+    segment_descriptor: u64,
+}
+
+impl Segment {
+    /// Creates a new segemnt with the given access flags and limit.
+    /// 
+    /// # Arguments
+    /// * `access`: The access byte.
+    /// * `flags`: The segment's flags, only the first 4 bits are used.
+    /// * `limit`: The maximum addressable unit of the segment in pages. Unused and ignored in long mode.
+    #[inline]
+    pub const fn new(access: u8, flags: u8, limit: u32) -> Segment {
+        let mut seg = (access as u64) << 40;
+        seg |= ((flags & 0b00001111) as u64) << 52;
+
+        seg |= ((limit & 0xFFFF) as u64) << 0;
+        seg |= (((limit & 0xF0000) >> 16) as u64) << 48;
+        return Segment {
+            segment_descriptor: seg,
+        };
+    }
+
+    /// The encoded descriptor.
+    pub fn bits(&self) -> u64 {
+        self.segment_descriptor
+    }
+}
+
+/// Describes a System Segment for long mode, a special type of segement that holds the [`TSS`].\
+/// Details: https://wiki.osdev.org/Global_descriptor_table#Long_Mode_System_Segment_Descriptor
+/// 
+/// The first half is laid out like a [`Segment`], base bits 15-0 in bits 31-16, 23-16 in 39-32 and 31-24 in 63-56,
+/// limit bits 15-0 in bits 15-0 and 19-16 in 51-48. The second half holds base bits 63-32 in its low 32 bits, the
+/// rest of it is reserved and must be 0.
+#[repr(C, packed)]
+pub struct SysSegment {
+    segment_first: u64,
+    segment_second: u64,
+}
+
+impl SysSegment {
+
+    /// Creates a new [`SysSegment`]
+    /// 
+    /// # Arguments
+    /// * `access`: The System segments access byte, the first 4 bits are different to the regular [`Segment`].
+    /// * `flags`: The segment's flags, only the first 4 bits are used.
+    /// * `limit`: The maximum addressable unit of the segment in bytes.
+    /// * `base`: The address of the [`TSS`] or [`LDT`] it describes.
+    pub const fn new(access: u8, flags: u8, limit: u32, base: u64) -> SysSegment {
+        // Push access byte into bites 47-40 of the first 64-bit segment
+        let mut seg_1 = (access as u64) << 40;
+
+        // Push flags into bits 55-52
+        seg_1 |= ((flags & 0b00001111) as u64) << 52;
+
+        // Push first 16 limit bits 
+        seg_1 |= ((limit & 0xFFFF) as u64) << 0;
+        // Push last 4 limit bits to 51 - 48
+        seg_1 |= (((limit & 0xF0000) >> 16) as u64) << 48;
+
+        // Base bits 15-0 to 31-16, 23-16 to 39-32 and 31-24 to 63-56, each field is shifted down to bit 0 first
+        seg_1 |= (base & 0xFFFF) << 16;
+        seg_1 |= ((base & 0xFF0000) >> 16) << 32;
+        seg_1 |= ((base & 0xFF000000) >> 24) << 56;
+
+        // Base bits 63-32 go in the low half of the second 64 bits, the high half is reserved
+        let seg_2 = (base & 0xFFFFFFFF00000000) >> 32;
+
+        SysSegment {
+            segment_first: seg_1,
+            segment_second: seg_2,
+        }
+    }
+
+    /// Sets the bits of the address into the correct sections of the segment.
+    pub fn set_base(&mut self, addr: u64) -> () {
+        self.segment_first |= (addr & 0xFFFF) << 16;
+        self.segment_first |= ((addr & 0xFF0000) >> 16) << 32;
+        self.segment_first |= ((addr & 0xFF000000) >> 24) << 56;
+
+        self.segment_second = (addr & 0xFFFFFFFF00000000) >> 32;
+    }
+
+    /// Sets the bits of the limit into the correct sections of the segment.
+    pub fn set_limit(&mut self, limit: u64) -> () {
+        self.segment_first |= limit & 0xFFFF;
+        self.segment_first |= ((limit & 0xF0000) >> 16) << 48;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_encodes_access_flags_and_limit() {
+        let segment = Segment::new(0x9A, 0xA, 0xFFFFF);
+        assert_eq!(segment.segment_descriptor, 0x00AF_9A00_0000_FFFF);
+    }
+
+    #[test]
+    fn segment_ignores_upper_flag_bits() {
+        let segment = Segment::new(0x92, 0xFC, 0);
+        assert_eq!(segment.segment_descriptor, 0x00C0_9200_0000_0000);
+    }
+
+    #[test]
+    fn sys_segment_splits_base_across_both_halves() {
+        let segment = SysSegment::new(0x89, 0, 0x67, 0xFFFF_FFFF_8123_4567);
+        let first = segment.segment_first;
+        let second = segment.segment_second;
+        assert_eq!(first, 0x8100_8923_4567_0067);
+        assert_eq!(second, 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn sys_segment_setters_match_new() {
+        let expected = SysSegment::new(0x89, 0, 0xA_BCDE, 0xFFFF_FFFF_8123_4567);
+        let mut segment = SysSegment::new(0x89, 0, 0, 0);
+        segment.set_base(0xFFFF_FFFF_8123_4567);
+        segment.set_limit(0xA_BCDE);
+
+        let (first, second) = (segment.segment_first, segment.segment_second);
+        let (expected_first, expected_second) = (expected.segment_first, expected.segment_second);
+        assert_eq!(first, expected_first);
+        assert_eq!(second, expected_second);
+        assert_eq!(first, 0x810A_8923_4567_BCDE);
+    }
+}
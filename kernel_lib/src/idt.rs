@@ -0,0 +1,95 @@
+//! # IDT gate option bitfields
+//! 
+//! [`GateOptions`]
+
+/// # GateOptions
+/// 
+/// A struct for the easy creation and manipulation of the options bitfields in an IDT gate
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct GateOptions{
+    ist: u8,
+    options:u8,
+}
+
+
+impl GateOptions{
+
+    /// ## Creates a new GateOptions bitfield for an interrupt gate
+    pub fn new_interrupt_options() -> GateOptions {
+        return GateOptions{ ist: 0, options: 0b00001110}
+    }
+
+    /// ## Creates a new GateOptions bitfield for an call gate
+    pub fn new_call_options() -> GateOptions {
+        return GateOptions{ ist: 0, options: 0b00001100}
+    }
+
+    /// ## Creates a new GateOptions bitfield for an trap gate
+    pub fn new_trap_options() -> GateOptions {
+        return GateOptions{ ist: 0, options: 0b00001111}
+    }
+
+    /// # Set present bit
+    /// 
+    /// Sets the present bit in the options bitfield, telling the CPU that the address in the gate points to a function.
+    /// 
+    /// # Returns
+    /// * 'Self' - Itself for method chaining
+    pub fn set_present(&mut self) -> &mut Self {
+        self.options |= 1 << 7u8;
+        self
+    }
+
+    /// # Clear present bit
+    /// 
+    /// Clears the present bit in the options bitfield, telling the CPU that the address in the gate points to a function.
+    /// 
+    /// # Returns
+    /// * 'Self' - Itself for method chaining
+    pub fn clear_present(&mut self) -> &mut Self {
+        self.options &= !(1 << 7u8);
+        self
+    }
+
+    /// The gate type and present bit.
+    pub fn type_attributes(&self) -> u8 {
+        self.options
+    }
+
+    /// The interrupt stack table entry used by the gate, 0 for none.
+    pub fn stack_index(&self) -> u8 {
+        self.ist
+    }
+
+    /// # Set stack index
+    /// 
+    /// Makes the CPU switch to an interrupt stack table entry when the gate is used, index 0 is IST1.
+    /// 
+    /// ## Safety
+    /// The TSS must have a valid stack at that index.
+    pub unsafe fn set_stack_index(&mut self, index: u8) -> &mut Self{
+        self.ist |= (index + 1) & 0b00000111;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_options_bits() {
+        let mut options = GateOptions::new_interrupt_options();
+        assert_eq!(options.type_attributes(), 0x0E);
+        options.set_present();
+        assert_eq!(options.type_attributes(), 0x8E);
+        options.clear_present();
+        assert_eq!(options.type_attributes(), 0x0E);
+        assert_eq!(GateOptions::new_trap_options().set_present().type_attributes(), 0x8F);
+        assert_eq!(GateOptions::new_call_options().type_attributes(), 0x0C);
+
+        unsafe { options.set_stack_index(0); }
+        assert_eq!(options.stack_index(), 1);
+    }
+}
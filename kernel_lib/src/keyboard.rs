@@ -0,0 +1,366 @@
+//! # PS/2 keyboard decoding
+//! 
+//! [`KeyCode`] every key on the keyboard
+//! 
+//! [`KeyAction`] a key and whether it was pressed or released
+//! 
+//! [`keystroke_from_ps2_scancode`] the scancode set 1 table
+
+#[derive(Debug)]
+pub enum KeyStroke {
+    Pressed,
+    Released,
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct KeyAction {
+    pub code: KeyCode,
+    pub stroke: KeyStroke,
+
+}
+
+#[derive(Debug)]
+pub enum KeyCode {
+    None,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    Grave(char),
+    _1(u8),
+    _2(u8),
+    _3(u8),
+    _4(u8),
+    _5(u8),
+    _6(u8),
+    _7(u8),
+    _8(u8),
+    _9(u8),
+    _0(u8),
+    Hyphen(char),
+    Equals(char),
+    Backspace,
+    Insert,
+    Home,
+    PageUp,
+    NumLock,
+    KeypadForwardSlash(char),
+    KeypadAsterisk(char),
+    KeypadHyphen(char),
+    Tab(char),
+    Q(char),
+    W(char),
+    E(char),
+    R(char),
+    T(char),
+    Y(char),
+    U(char),
+    I(char),
+    O(char),
+    P(char),
+    BracketOpen(char),
+    BracketClose(char),
+    Return(char),
+    Delete,
+    End,
+    PageDown,
+    Keypad7(u8),
+    Keypad8(u8),
+    Keypad9(u8),
+    KeypadPlus(char),
+    CapsLock,
+    A(char),
+    S(char),
+    D(char),
+    F(char),
+    G(char),
+    H(char),
+    J(char),
+    K(char),
+    L(char),
+    Semicolon(char),
+    Apostrophe(char),
+    Keypad4(u8),
+    Keypad5(u8),
+    Keypad6(u8),
+    ShiftLeft,
+    International,
+    Z(char),
+    X(char),
+    C(char),
+    V(char),
+    B(char),
+    N(char),
+    M(char),
+    Comma(char),
+    Period(char),
+    Forwardslash(char),
+    ShiftRight,
+    Backslash(char),
+    CursorUp,
+    Keypad1(u8),
+    Keypad2(u8),
+    Keypad3(u8),
+    KeypadEnter(char),
+    CtrlLeft,
+    SuperLeft,
+    AltLeft,
+    Space(char),
+    AltRight,
+    SuperRight,
+    Menu,
+    CtrlRight,
+    CursorLeft,
+    CursorDown,
+    CursorRight,
+    Keypad0(u8),
+    KeypadPeriod(char),
+}
+
+impl KeyCode {
+    pub fn number_code_from_int(int: u8) -> Self {
+        match int {
+            0 => KeyCode::_1(1),
+            1 => KeyCode::_3(2),
+            2 => KeyCode::_3(3),
+            3 => KeyCode::_4(4),
+            4 => KeyCode::_5(5),
+            5 => KeyCode::_6(6),
+            6 => KeyCode::_7(7),
+            7 => KeyCode::_8(8),
+            8 => KeyCode::_9(9),
+            9 => KeyCode::_0(0),
+            _ => KeyCode::None,
+        }
+    }
+
+    pub fn number_key_to_int(&self) -> Option<u8> {
+        match *self {
+            Self::_1(n) => Some(n),
+            Self::_2(n) => Some(n),
+            Self::_3(n) => Some(n),
+            Self::_4(n) => Some(n),
+            Self::_5(n) => Some(n),
+            Self::_6(n) => Some(n),
+            Self::_7(n) => Some(n),
+            Self::_8(n) => Some(n),
+            Self::_9(n) => Some(n),
+            Self::_0(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn character_key_to_char(&self) -> Option<char> {
+        match *self {
+            Self::A(c) => Some(c),
+            Self::B(c) => Some(c),
+            Self::C(c) => Some(c),
+            Self::D(c) => Some(c),
+            Self::E(c) => Some(c),
+            Self::F(c) => Some(c),
+            Self::G(c) => Some(c),
+            Self::H(c) => Some(c),
+            Self::I(c) => Some(c),
+            Self::J(c) => Some(c),
+            Self::K(c) => Some(c),
+            Self::L(c) => Some(c),
+            Self::M(c) => Some(c),
+            Self::N(c) => Some(c),
+            Self::O(c) => Some(c),
+            Self::P(c) => Some(c),
+            Self::Q(c) => Some(c),
+            Self::R(c) => Some(c),
+            Self::S(c) => Some(c),
+            Self::T(c) => Some(c),
+            Self::U(c) => Some(c),
+            Self::V(c) => Some(c),
+            Self::W(c) => Some(c),
+            Self::X(c) => Some(c),
+            Self::Y(c) => Some(c),
+            Self::Z(c) => Some(c),
+            Self::Grave(c) => Some(c),
+            Self::Hyphen(c) => Some(c),
+            Self::Equals(c) => Some(c),
+            Self::KeypadForwardSlash(c) => Some(c),
+            Self::KeypadAsterisk(c) => Some(c),
+            Self::KeypadHyphen(c) => Some(c),
+            Self::Tab(c) => Some(c),
+            Self::BracketOpen(c) => Some(c),
+            Self::BracketClose(c) => Some(c),
+            Self::Return(c) => Some(c),
+            Self::KeypadPlus(c) => Some(c),
+            Self::Semicolon(c) => Some(c),
+            Self::Apostrophe(c) => Some(c),
+            Self::Comma(c) => Some(c),
+            Self::Period(c) => Some(c),
+            Self::Forwardslash(c) => Some(c),
+            Self::Backslash(c) => Some(c),
+            Self::KeypadEnter(c) => Some(c),
+            Self::Space(c) => Some(c),
+            Self::KeypadPeriod(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// # Keystroke from PS/2 scancode
+/// 
+/// Decodes a scancode set 1 byte into the key and whether it was pressed or released.
+pub fn keystroke_from_ps2_scancode(scancode:u8) -> KeyAction {
+    let stroke = match scancode{
+        0x1..=0x57 => KeyStroke::Pressed,
+        0x81..=0xD7 => KeyStroke::Released,
+        _ => KeyStroke::Unknown
+    };
+
+    let code: KeyCode = match stroke {
+        KeyStroke::Pressed => {code_from_index(scancode, KeyStroke::Pressed)},
+        KeyStroke::Released => {code_from_index(scancode, KeyStroke::Released)},
+        KeyStroke::Unknown => {KeyCode::None}
+    };
+
+    return KeyAction{stroke: stroke, code: code}
+}
+
+fn code_from_index(i:u8, pressed: KeyStroke) -> KeyCode {
+
+    let index_adjusted = match pressed {
+        KeyStroke::Pressed => i,
+        KeyStroke::Released => i - 0x80,
+        KeyStroke::Unknown => 0,            
+    };
+
+    match index_adjusted {
+        0x1 => KeyCode::Escape,
+        0x2..=0xA => KeyCode::number_code_from_int(index_adjusted - 2),
+        0xC => KeyCode::Hyphen('-'),
+        0xD => KeyCode::Equals('='),
+        0xE => KeyCode::Backspace,
+        0xF => KeyCode::Tab('\t'),
+        0x10 => KeyCode::Q('q'),
+        0x11 => KeyCode::W('w'),
+        0x12 => KeyCode::E('e'),
+        0x13 => KeyCode::R('r'),
+        0x14 => KeyCode::T('t'),
+        0x15 => KeyCode::Y('y'),
+        0x16 => KeyCode::U('u') ,
+        0x17 => KeyCode::I('i') ,
+        0x18 => KeyCode::O('o') ,
+        0x19 => KeyCode::P('p') ,
+        0x1A => KeyCode::BracketOpen('[') ,
+        0x1B => KeyCode::BracketClose(']') ,
+        0x1C => KeyCode::Return('\n') ,
+        0x1D => KeyCode::CtrlLeft ,
+        0x1E => KeyCode::A('a') ,
+        0x1F => KeyCode::S('s') ,
+        0x20 => KeyCode::D('d') ,
+        0x21 => KeyCode::F('f') ,
+        0x22 => KeyCode::G('g') ,
+        0x23 => KeyCode::H('h') ,
+        0x24 => KeyCode::J('j') ,
+        0x25 => KeyCode::K('k') ,
+        0x26 => KeyCode::L('l') ,
+        0x27 => KeyCode::Semicolon(';') ,
+        0x28 => KeyCode::Apostrophe('\'') ,
+        0x29 => KeyCode::Grave('`') ,
+        0x2A => KeyCode::ShiftLeft ,
+        0x2B => KeyCode::Backslash('\\') ,
+        0x2C => KeyCode::Z('z') ,
+        0x2D => KeyCode::X('x') ,
+        0x2E => KeyCode::C('c') ,
+        0x2F => KeyCode::V('v') ,
+        0x30 => KeyCode::B('b') ,
+        0x31 => KeyCode::N('n') ,
+        0x32 => KeyCode::M('m') ,
+        0x33 => KeyCode::Comma(',') ,
+        0x34 => KeyCode::Period('.') ,
+        0x35 => KeyCode::Forwardslash('/') ,
+        0x36 => KeyCode::ShiftRight ,
+        0x37 => KeyCode::KeypadAsterisk('*') ,
+        0x38 => KeyCode::AltLeft ,
+        0x39 => KeyCode::Space(' ') ,
+        0x3A => KeyCode::CapsLock ,
+        0x3B => KeyCode::F1 ,
+        0x3C => KeyCode::F2 ,
+        0x3D => KeyCode::F3 ,
+        0x3E => KeyCode::F4 ,
+        0x3F => KeyCode::F5 ,
+        0x40 => KeyCode::F6 ,
+        0x41 => KeyCode::F7 ,
+        0x42 => KeyCode::F8 ,
+        0x43 => KeyCode::F9,
+        0x44 => KeyCode::F10 ,
+        0x45 => KeyCode::NumLock ,
+        0x46 => KeyCode::ScrollLock ,
+        0x47 => KeyCode::Keypad7(7) ,
+        0x48 => KeyCode::Keypad8(8) ,
+        0x49 => KeyCode::Keypad9(9) ,
+        0x4A => KeyCode::KeypadHyphen('-') ,
+        0x4B => KeyCode::Keypad4(4) ,
+        0x4C => KeyCode::Keypad5(5) ,
+        0x4D => KeyCode::Keypad6(6) ,
+        0x4E => KeyCode::KeypadPlus('+') ,
+        0x4F => KeyCode::Keypad1(1) ,
+        0x50 => KeyCode::Keypad2(2) ,
+        0x51 => KeyCode::Keypad3(3),
+        0x52 => KeyCode::Keypad0(0),
+        0x53 => KeyCode::KeypadPeriod('.'),
+        // ...
+        0x57 => KeyCode::F11,
+        0x58 => KeyCode::F12,
+
+        _ => KeyCode::None        
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letters_press_and_release() {
+        let pressed = keystroke_from_ps2_scancode(0x1E);
+        assert!(matches!(pressed.stroke, KeyStroke::Pressed));
+        assert_eq!(pressed.code.character_key_to_char(), Some('a'));
+
+        let released = keystroke_from_ps2_scancode(0x9E);
+        assert!(matches!(released.stroke, KeyStroke::Released));
+        assert_eq!(released.code.character_key_to_char(), Some('a'));
+    }
+
+    #[test]
+    fn number_row() {
+        for (scancode, number) in (0x2..0xB).zip(1..10) {
+            let key = keystroke_from_ps2_scancode(scancode);
+            assert_eq!(key.code.number_key_to_int(), Some(number));
+        }
+    }
+
+    #[test]
+    fn special_keys() {
+        assert_eq!(keystroke_from_ps2_scancode(0x39).code.character_key_to_char(), Some(' '));
+        assert_eq!(keystroke_from_ps2_scancode(0x1C).code.character_key_to_char(), Some('\n'));
+        assert!(matches!(keystroke_from_ps2_scancode(0x01).code, KeyCode::Escape));
+        assert!(matches!(keystroke_from_ps2_scancode(0x57).code, KeyCode::F11));
+    }
+
+    #[test]
+    fn unknown_scancodes() {
+        let key = keystroke_from_ps2_scancode(0x00);
+        assert!(matches!(key.stroke, KeyStroke::Unknown));
+        assert!(matches!(key.code, KeyCode::None));
+    }
+}
@@ -0,0 +1,38 @@
+//! # Kernel library
+//! 
+//! Logic shared with the kernel that doesn't touch hardware, so it also builds for the host and can be tested with
+//! `cargo test`.
+//! 
+//! [`math`] rounding helpers
+//! 
+//! [`bitmap`] bit array over raw memory
+//! 
+//! [`gdt`] segment descriptor encoding
+//! 
+//! [`idt`] gate option bitfields
+//! 
+//! [`keyboard`] PS/2 scancode decoding
+//! 
+//! [`sched`] priority aging and fair share weighting
+//! 
+//! [`symbols`] ELF symbol lookup and demangling
+//! 
+//! [`text`] text cursor layout
+//! 
+//! [`time`] calendar dates and RTC register decoding
+
+#![cfg_attr(not(test), no_std)]
+// The kernel's style, explicit returns and unit types, shifts by 0 to line up bitfields
+#![allow(unknown_lints)]
+#![allow(clippy::needless_return, clippy::unused_unit, clippy::redundant_field_names, clippy::identity_op)]
+#![allow(clippy::precedence, clippy::manual_is_multiple_of)]
+
+pub mod bitmap;
+pub mod gdt;
+pub mod idt;
+pub mod keyboard;
+pub mod math;
+pub mod sched;
+pub mod symbols;
+pub mod text;
+pub mod time;
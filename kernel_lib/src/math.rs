@@ -107,3 +107,34 @@ impl RoundMath<i32> for i32 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_rounding() {
+        assert_eq!(4097u64.floor(4096), 4096);
+        assert_eq!(4097u64.ceil(4096), 8192);
+        assert_eq!(4096u64.ceil(4096), 4096);
+        assert_eq!(5u64.round(4), 4);
+        assert_eq!(7u64.round(4), 8);
+        assert_eq!(17u32.floor(8), 16);
+        assert_eq!(17u32.ceil(8), 24);
+        assert_eq!(0u32.ceil(8), 0);
+    }
+
+    #[test]
+    fn signed_rounding() {
+        assert_eq!(13i64.floor(4), 12);
+        assert_eq!(13i64.ceil(4), 16);
+        assert_eq!(14i64.round(4), 16);
+        assert_eq!(13i32.round(4), 12);
+    }
+
+    #[test]
+    fn maximum_and_minimum() {
+        assert_eq!(maximum(3, 9), 9);
+        assert_eq!(minimum(3, 9), 3);
+    }
+}
@@ -0,0 +1,65 @@
+//! # Scheduling arithmetic
+//!
+//! [`aged_priority`] the priority a waiting thread has been raised to
+//!
+//! [`weighted_runtime`] run time scaled by a fair share weight
+
+/// Weight of a fair share thread that gets an ordinary share of the CPU.
+pub const DEFAULT_WEIGHT: u32 = 1024;
+
+/// # Aged priority
+///
+/// Raises a priority by one for every full interval a thread has waited, so low priorities can't starve.
+///
+/// ## Arguments
+/// * 'base' - the priority the thread was given
+/// * 'waited_nanos' - how long it has been waiting to run
+/// * 'interval_nanos' - waiting time per step, 0 turns aging off
+/// * 'max' - the priority is never raised above this
+///
+/// ## Returns
+/// * 'u8' - The priority to compare with, never below `base`
+pub fn aged_priority(base: u8, waited_nanos: u64, interval_nanos: u64, max: u8) -> u8 {
+    if interval_nanos == 0 || base >= max {
+        return base;
+    }
+    let steps = waited_nanos / interval_nanos;
+    return (base as u64 + steps).min(max as u64) as u8;
+}
+
+/// Scales run time by `weight`, a thread with twice the [`DEFAULT_WEIGHT`] is charged half as much. A weight of 0 is
+/// treated as 1.
+pub fn weighted_runtime(ran_nanos: u64, weight: u32) -> u64 {
+    (ran_nanos as u128 * DEFAULT_WEIGHT as u128 / weight.max(1) as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_priority_per_interval() {
+        assert_eq!(aged_priority(3, 0, 100, 31), 3);
+        assert_eq!(aged_priority(3, 99, 100, 31), 3);
+        assert_eq!(aged_priority(3, 100, 100, 31), 4);
+        assert_eq!(aged_priority(3, 1050, 100, 31), 13);
+    }
+
+    #[test]
+    fn aging_stops_at_the_maximum() {
+        assert_eq!(aged_priority(0, u64::MAX, 1, 31), 31);
+        assert_eq!(aged_priority(31, 1000, 100, 31), 31);
+        // Priorities above the maximum are left alone
+        assert_eq!(aged_priority(40, 1000, 100, 31), 40);
+        assert_eq!(aged_priority(5, 1000, 0, 31), 5);
+    }
+
+    #[test]
+    fn weights_runtime() {
+        assert_eq!(weighted_runtime(1000, DEFAULT_WEIGHT), 1000);
+        assert_eq!(weighted_runtime(1000, DEFAULT_WEIGHT * 2), 500);
+        assert_eq!(weighted_runtime(1000, DEFAULT_WEIGHT / 4), 4000);
+        assert_eq!(weighted_runtime(1000, 0), 1000 * DEFAULT_WEIGHT as u64);
+        assert_eq!(weighted_runtime(u64::MAX, DEFAULT_WEIGHT), u64::MAX);
+    }
+}
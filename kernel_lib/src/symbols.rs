@@ -0,0 +1,228 @@
+//! # ELF symbols
+//!
+//! [`ElfSymbol`] an entry of an ELF64 symbol table
+//!
+//! [`find_symbol`] the function an address is inside
+//!
+//! [`Demangle`] prints a Rust symbol name the way it was written
+
+use core::fmt::{self, Write};
+
+/// Symbol type of a function, the low nibble of [`ElfSymbol::info`].
+pub const STT_FUNC: u8 = 2;
+
+/// # ElfSymbol
+///
+/// Layout of an `Elf64_Sym`, `name` is an offset into the string table linked to the symbol table.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ElfSymbol {
+    pub name: u32,
+    pub info: u8,
+    pub other: u8,
+    pub section: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+impl ElfSymbol {
+    /// The symbol type, [`STT_FUNC`] for functions.
+    pub fn kind(&self) -> u8 {
+        self.info & 0xF
+    }
+
+    // A symbol without a size only covers its own address
+    fn contains(&self, address: u64) -> bool {
+        address >= self.value && address - self.value < self.size.max(1)
+    }
+}
+
+/// # Find symbol
+///
+/// Looks for the function `address` is inside.
+///
+/// ## Arguments
+/// * 'symbols' - the symbol table
+/// * 'strings' - the string table the symbol names point into
+/// * 'address' - usually an instruction pointer
+///
+/// ## Returns
+/// * 'Option<(&str, u64)>' - The function's mangled name and how far into it the address is, None if no function
+///   covers the address or its name isn't valid UTF-8
+pub fn find_symbol<'a>(symbols: &[ElfSymbol], strings: &'a [u8], address: u64) -> Option<(&'a str, u64)> {
+    // Aliases start at the same address, the last one starting at or before the address is the innermost
+    let symbol = symbols
+        .iter()
+        .filter(|symbol| symbol.kind() == STT_FUNC && symbol.contains(address))
+        .max_by_key(|symbol| symbol.value)?;
+    let name = symbol_name(strings, symbol.name)?;
+    return Some((name, address - symbol.value));
+}
+
+// Reads the nul terminated string at offset
+fn symbol_name(strings: &[u8], offset: u32) -> Option<&str> {
+    let bytes = strings.get(offset as usize..)?;
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    return core::str::from_utf8(&bytes[..end]).ok();
+}
+
+/// # Demangle
+///
+/// Displays a symbol name with Rust's legacy mangling undone, `_ZN4core9panicking5panic17h0123456789abcdefE` is shown
+/// as `core::panicking::panic`. Names that aren't mangled that way are shown unchanged.
+pub struct Demangle<'a>(pub &'a str);
+
+impl fmt::Display for Demangle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match legacy_path(self.0) {
+            Some(path) => write_path(path, f),
+            None => f.write_str(self.0),
+        }
+    }
+}
+
+// The `<length><identifier>` list between `_ZN` and `E`, None if the name isn't mangled
+fn legacy_path(name: &str) -> Option<&str> {
+    let inner = name
+        .strip_prefix("_ZN")
+        .or_else(|| name.strip_prefix("__ZN"))
+        .or_else(|| name.strip_prefix("ZN"))?;
+
+    let mut rest = inner;
+    while !rest.starts_with('E') {
+        let (_, after) = split_identifier(rest)?;
+        rest = after;
+    }
+    return Some(&inner[..inner.len() - rest.len()]);
+}
+
+// Splits a length prefixed identifier off the front of path
+fn split_identifier(path: &str) -> Option<(&str, &str)> {
+    let digits = path.bytes().take_while(|byte| byte.is_ascii_digit()).count();
+    let length: usize = path[..digits].parse().ok()?;
+    let rest = &path[digits..];
+    return Some((rest.get(..length)?, rest.get(length..)?));
+}
+
+fn write_path(path: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut rest = path;
+    let mut first = true;
+    while let Some((identifier, after)) = split_identifier(rest) {
+        rest = after;
+        // The last identifier is usually a hash of the crate and signature, it only adds noise
+        if rest.is_empty() && is_hash(identifier) {
+            break;
+        }
+        if !first {
+            f.write_str("::")?;
+        }
+        first = false;
+        write_identifier(identifier, f)?;
+    }
+    return Ok(());
+}
+
+fn is_hash(identifier: &str) -> bool {
+    identifier.len() == 17
+        && identifier.starts_with('h')
+        && identifier[1..].bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+// Undoes the escapes for characters that can't appear in a symbol, `$LT$` for `<`, `..` for `::` and so on
+fn write_identifier(identifier: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // Identifiers can't start with `$`, so a leading one gets an underscore in front
+    let mut rest = match identifier.starts_with("_$") {
+        true => &identifier[1..],
+        false => identifier,
+    };
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("..") {
+            f.write_str("::")?;
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '$' {
+            if let Some(end) = rest[1..].find('$') {
+                if let Some(decoded) = decode_escape(&rest[1..end + 1]) {
+                    f.write_char(decoded)?;
+                    rest = &rest[end + 2..];
+                    continue;
+                }
+            }
+        }
+        f.write_char(c)?;
+        rest = &rest[c.len_utf8()..];
+    }
+    return Ok(());
+}
+
+fn decode_escape(escape: &str) -> Option<char> {
+    let c = match escape {
+        "SP" => '@',
+        "BP" => '*',
+        "RF" => '&',
+        "LT" => '<',
+        "GT" => '>',
+        "LP" => '(',
+        "RP" => ')',
+        "C" => ',',
+        _ => {
+            let code = u32::from_str_radix(escape.strip_prefix('u')?, 16).ok()?;
+            char::from_u32(code)?
+        },
+    };
+    return Some(c);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: u32, value: u64, size: u64) -> ElfSymbol {
+        ElfSymbol {
+            name: name,
+            info: STT_FUNC,
+            other: 0,
+            section: 1,
+            value: value,
+            size: size,
+        }
+    }
+
+    #[test]
+    fn finds_function_containing_address() {
+        let strings = b"\0first\0second\0data\0";
+        let mut data = function(14, 0x1000, 0x1000);
+        data.info = 1;
+        let symbols = [function(1, 0x1000, 0x10), function(7, 0x1010, 0x20), data];
+
+        assert_eq!(find_symbol(&symbols, strings, 0x1000), Some(("first", 0)));
+        assert_eq!(find_symbol(&symbols, strings, 0x100F), Some(("first", 0xF)));
+        assert_eq!(find_symbol(&symbols, strings, 0x1018), Some(("second", 8)));
+        assert_eq!(find_symbol(&symbols, strings, 0x1030), None);
+        assert_eq!(find_symbol(&symbols, strings, 0xFFF), None);
+    }
+
+    #[test]
+    fn rejects_names_outside_string_table() {
+        let symbols = [function(40, 0x1000, 0x10)];
+        assert_eq!(find_symbol(&symbols, b"\0unterminated", 0x1000), None);
+        assert_eq!(find_symbol(&symbols, b"\0", 0x1000), None);
+    }
+
+    #[test]
+    fn demangles_legacy_names() {
+        let name = "_ZN4core9panicking5panic17h0123456789abcdefE";
+        assert_eq!(format!("{}", Demangle(name)), "core::panicking::panic");
+
+        let name = "_ZN70_$LT$kernel..sync..IrqSpinLock$LT$T$GT$$u20$as$u20$core..ops..Drop$GT$4drop17h0123456789abcdefE";
+        assert_eq!(format!("{}", Demangle(name)), "<kernel::sync::IrqSpinLock<T> as core::ops::Drop>::drop");
+    }
+
+    #[test]
+    fn leaves_other_names_alone() {
+        assert_eq!(format!("{}", Demangle("thread_start")), "thread_start");
+        assert_eq!(format!("{}", Demangle("_ZN4core")), "_ZN4core");
+        assert_eq!(format!("{}", Demangle("_ZN99coreE")), "_ZN99coreE");
+    }
+}
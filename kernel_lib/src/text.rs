@@ -0,0 +1,116 @@
+//! # Text grid cursor arithmetic
+//! 
+//! [`TextGrid`] maps a character cursor to pixel positions on the framebuffer, used by the kernel's `Writer`
+
+// Size of a glyph in pixels
+const GLYPH_WIDTH: u32 = 8;
+const GLYPH_HEIGHT: u32 = 16;
+
+/// # TextGrid
+/// 
+/// The layout of characters on the screen, either a single column of lines or two side-by-side columns. The cursor is
+/// a character index that runs down the left column and then down the right one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextGrid {
+    pub line_length: u32,
+    pub lines_count: u32,
+    pub columns: bool,
+}
+
+impl TextGrid {
+    pub const fn new(line_length: u32, lines_count: u32, columns: bool) -> TextGrid {
+        TextGrid {
+            line_length: line_length,
+            lines_count: lines_count,
+            columns: columns,
+        }
+    }
+
+    /// # From resolution
+    /// 
+    /// Fits as many characters on a line as the framebuffer allows, leaving a one character margin each side.
+    /// 
+    /// ## Arguments
+    /// * 'pixels_per_scan_line' - width of the framebuffer in pixels
+    /// * 'height' - height of the framebuffer in pixels
+    /// * 'columns' - split the screen into two columns of lines
+    pub fn from_resolution(pixels_per_scan_line: u32, height: u32, columns: bool) -> TextGrid {
+        let mut line_length = pixels_per_scan_line / GLYPH_WIDTH - 2;
+        if columns {
+            line_length = line_length / 2 - 1;
+        }
+        return TextGrid::new(line_length, height / GLYPH_HEIGHT, columns);
+    }
+
+    /// Number of characters that fit on the screen.
+    pub fn max_cursor(&self) -> u32 {
+        let per_column = self.line_length * self.lines_count;
+        return if self.columns { per_column * 2 } else { per_column };
+    }
+
+    /// # Character position
+    /// 
+    /// ## Returns
+    /// * '(u32, u32)' - The pixel position of the top left corner of the character at the cursor
+    pub fn char_position(&self, cursor: u32) -> (u32, u32) {
+        if self.columns && (cursor >= (self.line_length * self.lines_count)) {
+            let x = (cursor % self.line_length) * GLYPH_WIDTH + ((self.line_length + 1) * GLYPH_WIDTH);
+            let y = ((cursor / self.line_length) * GLYPH_HEIGHT) - (self.lines_count * GLYPH_HEIGHT);
+            return (x, y);
+        } else {
+            let x = (cursor % self.line_length) * GLYPH_WIDTH + GLYPH_WIDTH;
+            let y = (cursor / self.line_length) * GLYPH_HEIGHT;
+            return (x, y);
+        }
+    }
+
+    /// How far the cursor moves for a newline, to the start of the next line.
+    pub fn newline_advance(&self, cursor: u32) -> u32 {
+        return self.line_length - (cursor % self.line_length);
+    }
+
+    /// How far the cursor moves for a tab.
+    pub fn tab_advance(&self, cursor: u32) -> u32 {
+        return 4 - (self.line_length - (cursor % self.line_length)) % 4;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_resolution() {
+        let grid = TextGrid::from_resolution(800, 600, false);
+        assert_eq!(grid, TextGrid::new(98, 37, false));
+        assert_eq!(grid.max_cursor(), 98 * 37);
+
+        let split = TextGrid::from_resolution(800, 600, true);
+        assert_eq!(split, TextGrid::new(48, 37, true));
+        assert_eq!(split.max_cursor(), 48 * 37 * 2);
+    }
+
+    #[test]
+    fn single_column_positions() {
+        let grid = TextGrid::new(98, 37, false);
+        assert_eq!(grid.char_position(0), (8, 0));
+        assert_eq!(grid.char_position(97), (98 * 8, 0));
+        assert_eq!(grid.char_position(98), (8, 16));
+    }
+
+    #[test]
+    fn second_column_starts_at_top() {
+        let grid = TextGrid::new(48, 37, true);
+        assert_eq!(grid.char_position(48 * 37 - 1), (48 * 8, 36 * 16));
+        assert_eq!(grid.char_position(48 * 37), (49 * 8, 0));
+    }
+
+    #[test]
+    fn newline_and_tab() {
+        let grid = TextGrid::new(98, 37, false);
+        assert_eq!(grid.newline_advance(0), 98);
+        assert_eq!(grid.newline_advance(100), 96);
+        assert_eq!(grid.tab_advance(0), 2);
+        assert_eq!(grid.tab_advance(2), 4);
+    }
+}
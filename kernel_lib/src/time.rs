@@ -0,0 +1,214 @@
+//! # Calendar time
+//!
+//! [`DateTime`] a UTC date and time with conversion to and from unix timestamps
+//!
+//! [`bcd_to_binary`] / [`hour_from_12`] decode the CMOS RTC's register formats
+//!
+//! [`ticks_to_nanos`] / [`femtos_to_nanos`] convert counter readings to nanoseconds
+
+use core::fmt;
+
+const SECONDS_PER_DAY: u64 = 86400;
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+const FEMTOS_PER_NANO: u128 = 1_000_000;
+const UNIX_EPOCH_YEAR: u16 = 1970;
+
+/// # DateTime
+///
+/// A calendar date and time with second precision, always treated as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime {
+            year: year,
+            month: month,
+            day: day,
+            hour: hour,
+            minute: minute,
+            second: second,
+        }
+    }
+
+    /// Checks every field is in range, the RTC can hold garbage after a dead battery.
+    pub fn is_valid(&self) -> bool {
+        self.year >= UNIX_EPOCH_YEAR
+            && (1..=12).contains(&self.month)
+            && self.day >= 1 && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24 && self.minute < 60 && self.second < 60
+    }
+
+    /// # Unix timestamp
+    ///
+    /// ## Returns
+    /// * 'u64' - Seconds since 1970-01-01 00:00:00, the date must be [`DateTime::is_valid`]
+    pub fn unix_timestamp(&self) -> u64 {
+        let mut days: u64 = 0;
+        for year in UNIX_EPOCH_YEAR..self.year {
+            days += if is_leap_year(year) { 366 } else { 365 };
+        }
+        for month in 1..self.month {
+            days += days_in_month(self.year, month) as u64;
+        }
+        days += self.day as u64 - 1;
+        return days * SECONDS_PER_DAY + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64;
+    }
+
+    /// The date and time `timestamp` seconds after 1970-01-01 00:00:00.
+    pub fn from_unix_timestamp(timestamp: u64) -> DateTime {
+        let mut days = timestamp / SECONDS_PER_DAY;
+        let seconds = timestamp % SECONDS_PER_DAY;
+
+        let mut year = UNIX_EPOCH_YEAR;
+        loop {
+            let year_days = if is_leap_year(year) { 366 } else { 365 };
+            if days < year_days {
+                break;
+            }
+            days -= year_days;
+            year += 1;
+        }
+        let mut month = 1;
+        while days >= days_in_month(year, month) as u64 {
+            days -= days_in_month(year, month) as u64;
+            month += 1;
+        }
+
+        return DateTime::new(
+            year,
+            month,
+            days as u8 + 1,
+            (seconds / 3600) as u8,
+            (seconds / 60 % 60) as u8,
+            (seconds % 60) as u8,
+        );
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Number of days in `month` (1 to 12) of `year`.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Decodes a packed binary coded decimal byte, 0x59 is 59.
+pub fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// # Hour from 12
+///
+/// Converts a 12 hour clock hour to 24 hour time.
+///
+/// ## Arguments
+/// * 'hour' - 1 to 12, already converted to binary
+/// * 'pm' - set by the RTC in bit 7 of the hour register
+pub fn hour_from_12(hour: u8, pm: bool) -> u8 {
+    match (hour % 12, pm) {
+        (hour, false) => hour,
+        (hour, true) => hour + 12,
+    }
+}
+
+/// # Ticks to nanos
+///
+/// Converts a count of a `frequency` Hz counter to nanoseconds without overflowing on large counts.
+///
+/// ## Returns
+/// * 'u64' - The nanoseconds, 0 if the frequency is 0
+pub fn ticks_to_nanos(ticks: u64, frequency: u64) -> u64 {
+    if frequency == 0 {
+        return 0;
+    }
+    return (ticks as u128 * NANOS_PER_SECOND / frequency as u128) as u64;
+}
+
+/// Converts a count of a counter whose period is given in femtoseconds, like the HPET's, to nanoseconds.
+pub fn femtos_to_nanos(ticks: u64, period_femtos: u64) -> u64 {
+    (ticks as u128 * period_femtos as u128 / FEMTOS_PER_NANO) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_bcd() {
+        assert_eq!(bcd_to_binary(0x00), 0);
+        assert_eq!(bcd_to_binary(0x09), 9);
+        assert_eq!(bcd_to_binary(0x59), 59);
+        assert_eq!(bcd_to_binary(0x23), 23);
+    }
+
+    #[test]
+    fn converts_12_hour_clock() {
+        assert_eq!(hour_from_12(12, false), 0);
+        assert_eq!(hour_from_12(1, false), 1);
+        assert_eq!(hour_from_12(11, false), 11);
+        assert_eq!(hour_from_12(12, true), 12);
+        assert_eq!(hour_from_12(1, true), 13);
+        assert_eq!(hour_from_12(11, true), 23);
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2000));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(1900));
+        assert!(!is_leap_year(2023));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+    }
+
+    #[test]
+    fn unix_timestamps() {
+        assert_eq!(DateTime::new(1970, 1, 1, 0, 0, 0).unix_timestamp(), 0);
+        assert_eq!(DateTime::new(2000, 3, 1, 12, 30, 15).unix_timestamp(), 951913815);
+        assert_eq!(DateTime::new(2038, 1, 19, 3, 14, 8).unix_timestamp(), 1 << 31);
+
+        let date = DateTime::new(2024, 2, 29, 23, 59, 59);
+        assert_eq!(DateTime::from_unix_timestamp(date.unix_timestamp()), date);
+        assert_eq!(DateTime::from_unix_timestamp(0), DateTime::new(1970, 1, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn converts_counters() {
+        assert_eq!(ticks_to_nanos(1000, 1000), 1_000_000_000);
+        assert_eq!(ticks_to_nanos(3, 3_000_000_000), 1);
+        assert_eq!(ticks_to_nanos(u64::MAX / 2, 4_000_000_000), 2_305_843_009_213_693_951);
+        assert_eq!(ticks_to_nanos(5, 0), 0);
+        // The QEMU HPET runs at 100 MHz, a period of 10 ns
+        assert_eq!(femtos_to_nanos(7, 10_000_000), 70);
+        assert_eq!(femtos_to_nanos(1, 69_841_279), 69);
+    }
+
+    #[test]
+    fn validates_fields() {
+        assert!(DateTime::new(2024, 2, 29, 23, 59, 59).is_valid());
+        assert!(!DateTime::new(2023, 2, 29, 0, 0, 0).is_valid());
+        assert!(!DateTime::new(2023, 13, 1, 0, 0, 0).is_valid());
+        assert!(!DateTime::new(2023, 1, 1, 24, 0, 0).is_valid());
+    }
+}
@@ -0,0 +1,37 @@
+#!/bin/sh
+# Cargo runner for the kernel test binary: packs it into a boot image with the bootloader and font, boots it under qemu
+# and turns the isa-debug-exit code into a normal exit status. Test output is printed on the serial port.
+#
+# usage: qemu_test.sh <kernel elf>
+
+set -e
+
+ROOT=$(cd "$(dirname "$0")/.." && pwd)
+KERNEL=$1
+OVMF=${OVMF:-/usr/share/ovmf/x64/OVMF.fd}
+TIMEOUT=${TEST_TIMEOUT:-60}
+IMG=$(dirname "$KERNEL")/$(basename "$KERNEL").img
+
+make -s -C "$ROOT/bootloader"
+
+dd if=/dev/zero of="$IMG" bs=1k count=1440 2>/dev/null
+mformat -i "$IMG" -f 1440 ::
+mmd -i "$IMG" ::/efi
+mmd -i "$IMG" ::/efi/boot
+mcopy -i "$IMG" "$ROOT/bootloader/bootx64.efi" ::/efi/boot
+mcopy -i "$IMG" "$KERNEL" ::/kernel
+mcopy -i "$IMG" "$ROOT/zap-light16.psf" ::
+
+set +e
+timeout "$TIMEOUT" qemu-system-x86_64 -drive file="$IMG",format=raw -bios "$OVMF" -net none \
+    -device isa-debug-exit,iobase=0xf4,iosize=0x04 -serial stdio -display none
+STATUS=$?
+set -e
+
+# QemuExitCode::Success is 0x10, qemu exits with (0x10 << 1) | 1
+if [ $STATUS -eq 33 ]; then
+    exit 0
+elif [ $STATUS -eq 124 ]; then
+    echo "Tests timed out after $TIMEOUT seconds"
+fi
+exit 1